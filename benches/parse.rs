@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+use std::{convert::TryFrom, str::FromStr};
+
+/// Number of full-size IDAT chunks in the fixture, giving roughly 1MiB of image data
+const IMAGE_DATA_CHUNKS: usize = 128;
+
+fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+}
+
+/// Representative file: a header, a handful of ancillary chunks, image data split at libpng's
+/// default size, a hidden message and the end marker
+fn fixture() -> Png {
+    let mut chunks = vec![
+        chunk("IHDR", vec![0, 0, 2, 0, 0, 0, 2, 0, 8, 6, 0, 0, 0]),
+        chunk("sRGB", vec![0]),
+        chunk("gAMA", vec![0, 0, 177, 143]),
+        chunk("tEXt", b"Software\0pngme".to_vec()),
+    ];
+
+    for i in 0..IMAGE_DATA_CHUNKS {
+        let data = (0..Png::IMAGE_DATA_CHUNK_SIZE)
+            .map(|b| (b * 31 + i) as u8)
+            .collect();
+        chunks.push(chunk(Png::IMAGE_DATA_CHUNK_TYPE, data));
+    }
+
+    chunks.push(chunk("ruSt", b"This is a secret message!".to_vec()));
+    chunks.push(chunk(Png::END_CHUNK_TYPE, Vec::new()));
+
+    Png::from_chunks(chunks)
+}
+
+fn bench_chunk(c: &mut Criterion) {
+    let image_data = chunk(
+        Png::IMAGE_DATA_CHUNK_TYPE,
+        vec![0xAB; Png::IMAGE_DATA_CHUNK_SIZE],
+    );
+    let bytes = image_data.as_bytes();
+
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| Chunk::try_from(black_box(bytes.as_slice())).unwrap())
+    });
+    group.bench_function("crc", |b| b.iter(|| black_box(&image_data).crc()));
+    group.bench_function("serialize", |b| {
+        b.iter(|| black_box(&image_data).as_bytes())
+    });
+    group.finish();
+}
+
+fn bench_png(c: &mut Criterion) {
+    let png = fixture();
+    let bytes = png.as_bytes();
+
+    let mut group = c.benchmark_group("png");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+    });
+    group.bench_function("serialize", |b| b.iter(|| black_box(&png).as_bytes()));
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut png = fixture();
+
+    c.bench_function("png/append_and_remove", |b| {
+        b.iter(|| {
+            png.append_chunk(chunk("teSt", b"message".to_vec()));
+            png.remove_chunk("teSt").unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_chunk, bench_png, bench_encode);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk::Chunk;
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chunk) = Chunk::try_from(data) {
+        // anything that parses must serialize back to the bytes it was parsed from
+        let bytes = chunk.as_bytes();
+        assert_eq!(&data[..bytes.len()], &bytes[..]);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk_type::ChunkType;
+use std::str::FromStr;
+
+fuzz_target!(|data: &str| {
+    if let Ok(chunk_type) = ChunkType::from_str(data) {
+        assert_eq!(chunk_type.to_string(), data);
+    }
+});
@@ -0,0 +1,239 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+use std::{convert::TryFrom, fmt::Display, str::FromStr};
+
+/// Key/value provenance attached to a PNG (source, operator, case number etc), stored in its
+/// own private chunk so that it's kept separate from any secret payload
+#[derive(Debug, Default, PartialEq)]
+pub struct Annotations {
+    entries: Vec<(String, String)>,
+}
+
+impl Annotations {
+    /// Ancillary, private, safe-to-copy chunk type used to store annotations
+    pub const CHUNK_TYPE: &'static str = "anOt";
+
+    /// Create an empty set of annotations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of annotations
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no annotations
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add an annotation, replacing the value of any existing annotation with the same key
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<()> {
+        if key.is_empty() || key.contains('\0') {
+            return Err(Box::from(AnnotationError::InvalidKey(key.to_string())));
+        }
+
+        if value.contains('\0') {
+            return Err(Box::from(AnnotationError::InvalidValue(key.to_string())));
+        }
+
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.entries.push((key.to_string(), value.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Value of the annotation with the given key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Remove the annotation with the given key, returning its value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// All annotations, in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Annotations whose key starts with the given prefix
+    pub fn with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.iter().filter(move |(k, _)| k.starts_with(prefix))
+    }
+
+    /// Encode the annotations as a chunk. Each entry is stored as `key\0value\0`
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = self
+            .entries
+            .iter()
+            .flat_map(|(k, v)| {
+                k.bytes()
+                    .chain(std::iter::once(0))
+                    .chain(v.bytes())
+                    .chain(std::iter::once(0))
+            })
+            .collect();
+
+        let chunk_type =
+            ChunkType::from_str(Annotations::CHUNK_TYPE).expect("annotation chunk type is valid");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Annotations {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if !chunk.chunk_type().matches(Annotations::CHUNK_TYPE) {
+            return Err(Box::from(AnnotationError::WrongChunkType));
+        }
+
+        let data = chunk.data();
+
+        if data.is_empty() {
+            return Ok(Annotations::new());
+        }
+
+        // every key and value is terminated, so the final split is always empty
+        let fields: Vec<&[u8]> = data.split(|&b| b == 0).collect();
+        let (last, fields) = fields.split_last().expect("split always yields a field");
+
+        if !last.is_empty() || fields.len() % 2 != 0 {
+            return Err(Box::from(AnnotationError::Malformed));
+        }
+
+        let mut annotations = Annotations::new();
+
+        for pair in fields.chunks(2) {
+            let key = std::str::from_utf8(pair[0])?;
+            let value = std::str::from_utf8(pair[1])?;
+            annotations.insert(key, value)?;
+        }
+
+        Ok(annotations)
+    }
+}
+
+/// Annotation errors
+#[derive(Debug)]
+pub enum AnnotationError {
+    /// Keys must be non-empty and can't contain NUL characters
+    InvalidKey(String),
+
+    /// Values can't contain NUL characters
+    InvalidValue(String),
+
+    /// The chunk isn't an annotation chunk
+    WrongChunkType,
+
+    /// The chunk data isn't a sequence of NUL-terminated key/value pairs
+    Malformed,
+}
+
+impl std::error::Error for AnnotationError {}
+
+impl Display for AnnotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnnotationError::InvalidKey(key) => write!(
+                f,
+                "Invalid annotation key '{}'. Keys must be non-empty and can't contain NUL",
+                key
+            ),
+            AnnotationError::InvalidValue(key) => write!(
+                f,
+                "Invalid value for annotation '{}'. Values can't contain NUL",
+                key
+            ),
+            AnnotationError::WrongChunkType => write!(
+                f,
+                "Annotations must be stored in a '{}' chunk",
+                Annotations::CHUNK_TYPE
+            ),
+            AnnotationError::Malformed => write!(f, "Malformed annotation chunk"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_annotations() -> Annotations {
+        let mut annotations = Annotations::new();
+        annotations.insert("source", "camera-7").unwrap();
+        annotations.insert("case.number", "2020-114").unwrap();
+        annotations.insert("case.operator", "arodger").unwrap();
+        annotations
+    }
+
+    #[test]
+    fn test_insert_replaces_existing_key() {
+        let mut annotations = testing_annotations();
+        annotations.insert("source", "scanner").unwrap();
+
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(annotations.get("source"), Some("scanner"));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let mut annotations = Annotations::new();
+        assert!(annotations.insert("", "value").is_err());
+        assert!(annotations.insert("k\0ey", "value").is_err());
+        assert!(annotations.insert("key", "val\0ue").is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut annotations = testing_annotations();
+        assert_eq!(annotations.remove("source"), Some(String::from("camera-7")));
+        assert_eq!(annotations.get("source"), None);
+        assert_eq!(annotations.remove("source"), None);
+    }
+
+    #[test]
+    fn test_with_prefix() {
+        let annotations = testing_annotations();
+        let keys: Vec<&str> = annotations.with_prefix("case.").map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["case.number", "case.operator"]);
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let annotations = testing_annotations();
+        let chunk = annotations.to_chunk();
+
+        assert_eq!(&chunk.chunk_type().to_string(), "anOt");
+        assert_eq!(Annotations::try_from(&chunk).unwrap(), annotations);
+    }
+
+    #[test]
+    fn test_empty_chunk_round_trip() {
+        let chunk = Annotations::new().to_chunk();
+        assert!(Annotations::try_from(&chunk).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_chunk() {
+        let chunk_type = ChunkType::from_str(Annotations::CHUNK_TYPE).unwrap();
+        let chunk = Chunk::new(chunk_type, b"key\0value".to_vec());
+        assert!(Annotations::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_wrong_chunk_type() {
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), Vec::new());
+        assert!(Annotations::try_from(&chunk).is_err());
+    }
+}
@@ -1 +1,680 @@
+use pngme::chunk_type::ChunkType;
+use pngme::parse_options::ParseOptions;
+use pngme::strip::StripPreset;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+};
+use structopt::StructOpt;
 
+/// Hide secret messages in PNG files
+#[derive(Debug, StructOpt)]
+#[structopt(name = "pngme")]
+pub struct PngMeArgs {
+    #[structopt(flatten)]
+    pub parse: ParseArgs,
+
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+// Limits applied when reading PNG files, so a malicious file can't make pngme allocate unbounded
+// memory. This isn't a doc comment because structopt would use it as the app's description
+#[derive(Debug, StructOpt)]
+pub struct ParseArgs {
+    /// Largest PNG file to read, in bytes with an optional K, M or G suffix [default: 256M]
+    #[structopt(long, global = true, parse(try_from_str = byte_size))]
+    pub max_file_size: Option<usize>,
+
+    /// Largest number of chunks to read from a single file [default: 100000]
+    #[structopt(long, global = true)]
+    pub max_chunks: Option<usize>,
+
+    /// Largest data length of a single chunk, in bytes with an optional K, M or G suffix
+    /// [default: 64M]
+    #[structopt(long, global = true, parse(try_from_str = byte_size))]
+    pub max_chunk_length: Option<usize>,
+
+    /// Accept chunks with an all-zero placeholder CRC, reporting them as warnings. The correct
+    /// CRC is written if the file is saved
+    #[structopt(long, global = true)]
+    pub lenient_crc: bool,
+
+    /// Recover what's available of a final chunk which runs past the end of the file, e.g. from
+    /// a truncated download, reporting it as a warning
+    #[structopt(long, global = true)]
+    pub lenient_truncation: bool,
+}
+
+impl ParseArgs {
+    /// The default parse options with any limits given on the command line applied
+    pub fn options(&self) -> ParseOptions {
+        let defaults = ParseOptions::default();
+        ParseOptions {
+            max_file_size: self.max_file_size.unwrap_or(defaults.max_file_size),
+            max_chunks: self.max_chunks.unwrap_or(defaults.max_chunks),
+            max_chunk_length: self.max_chunk_length.unwrap_or(defaults.max_chunk_length),
+            lenient_crc: self.lenient_crc,
+            lenient_truncation: self.lenient_truncation,
+            ..defaults
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Encode a message into a PNG file, or embed XMP metadata, printing how much the file grew.
+    /// By default a second chunk is added if the file already has one of the same type; see --mode
+    #[structopt(visible_alias = "e")]
+    Encode(EncodeArgs),
+
+    /// Print the message stored in a chunk of a PNG file
+    #[structopt(visible_alias = "d")]
+    Decode(DecodeArgs),
+
+    /// Remove a chunk from a PNG file, printing the message it held. Selectors remove every
+    /// matching ancillary chunk instead, printing their types; when combined a chunk must match
+    /// all of them. Critical chunks are never removed
+    #[structopt(visible_alias = "r")]
+    Remove(RemoveArgs),
+
+    /// List the chunks of a PNG file
+    #[structopt(visible_alias = "p")]
+    Print(PrintArgs),
+
+    /// Write a whole chunk, including its length, type and CRC, so it can be injected elsewhere
+    Dump(DumpArgs),
+
+    /// Insert a whole chunk, as written by dump or another tool, into a PNG file
+    Inject(InjectArgs),
+
+    /// Move a chunk to a new position without changing its data, e.g. for decoders which expect
+    /// ancillary chunks before the image data
+    Move(MoveArgs),
+
+    /// Extract data appended to a PNG file by other tools, or its XMP metadata
+    Extract(ExtractArgs),
+
+    /// List the frames of an animated PNG file, or extract them
+    Frames(FramesArgs),
+
+    /// Move ICC colour profiles in and out of PNG files
+    Icc(IccArgs),
+
+    /// Attach provenance annotations to a PNG file, or query them
+    Annotate(AnnotateArgs),
+
+    /// Remove metadata from a PNG file, printing the types of the chunks removed
+    Strip(StripArgs),
+
+    /// Remove everything not needed to decode a PNG file and normalise the rest, so it can't be
+    /// fingerprinted back to the tool which produced it
+    Anonymize(AnonymizeArgs),
+
+    /// Generate a carrier image, for when there's no suitable image at hand
+    New(NewArgs),
+
+    /// Check that each kind of edit leaves an image's pixels unchanged and still readable
+    Selftest(SelftestArgs),
+
+    // any other command runs pngme-<command> from PATH, like git does, so the CLI can be
+    // extended without forking it
+    #[structopt(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct EncodeArgs {
+    /// PNG file to encode the message into
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Ancillary chunk type to store the message in, e.g. ruSt. Only optional with --xmp-file
+    #[structopt(required_unless = "xmp-file", requires = "message")]
+    pub chunk_type: Option<ChunkType>,
+
+    /// Message to encode
+    #[structopt(parse(try_from_os_str = utf8_argument))]
+    pub message: Option<String>,
+
+    /// Where to write the result, instead of overwriting the input file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// XMP metadata packet to embed, replacing any existing packet
+    #[structopt(long, parse(from_os_str))]
+    pub xmp_file: Option<PathBuf>,
+
+    /// What to do when the file already has a chunk of this type: append a second chunk and
+    /// keep the existing one, replace the existing one, or fail
+    #[structopt(long, default_value = "append", possible_values = &EncodeMode::VARIANTS)]
+    pub mode: EncodeMode,
+
+    #[structopt(flatten)]
+    pub trailing: TrailingArgs,
+
+    /// Fail without writing if the decoded image data would change
+    #[structopt(long)]
+    pub verify_pixels: bool,
+
+    /// Fail without writing if the file would grow by more than this, either in bytes (e.g. 4K)
+    /// or as a percentage of its size (e.g. 2%)
+    #[structopt(long, visible_alias = "assert-max-growth")]
+    pub max_overhead: Option<SizeBudget>,
+}
+
+/// How encode treats an existing chunk of the same type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeMode {
+    Append,
+    Replace,
+    Error,
+}
+
+impl EncodeMode {
+    pub const VARIANTS: [&'static str; 3] = ["append", "replace", "error"];
+}
+
+impl FromStr for EncodeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "append" => Ok(EncodeMode::Append),
+            "replace" => Ok(EncodeMode::Replace),
+            "error" => Ok(EncodeMode::Error),
+            _ => Err(format!("Unknown encode mode '{}'", s)),
+        }
+    }
+}
+
+/// Most a file may grow by when it's rewritten
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeBudget {
+    Bytes(usize),
+    Percent(f64),
+}
+
+impl SizeBudget {
+    /// Whether growing from the first size to the second stays within the budget
+    pub fn allows(&self, before: usize, after: usize) -> bool {
+        let growth = after.saturating_sub(before);
+
+        match *self {
+            SizeBudget::Bytes(max) => growth <= max,
+            SizeBudget::Percent(max) => growth as f64 <= before as f64 * max / 100.0,
+        }
+    }
+}
+
+impl FromStr for SizeBudget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => percent
+                .parse::<f64>()
+                .ok()
+                .filter(|p| p.is_finite() && *p >= 0.0)
+                .map(SizeBudget::Percent)
+                .ok_or_else(|| format!("'{}' isn't a percentage, e.g. 2%", s)),
+            None => byte_size(s).map(SizeBudget::Bytes),
+        }
+    }
+}
+
+impl Display for SizeBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeBudget::Bytes(max) => write!(f, "{} bytes", max),
+            SizeBudget::Percent(max) => write!(f, "{}%", max),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct DecodeArgs {
+    /// PNG file to decode the message from
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Chunk type the message is stored in
+    pub chunk_type: ChunkType,
+
+    #[structopt(flatten)]
+    pub payload: PayloadArgs,
+}
+
+/// What happens to data appended after the IEND chunk when a file is rewritten. By default it's
+/// kept, with a warning
+#[derive(Debug, Default, StructOpt)]
+pub struct TrailingArgs {
+    /// Keep any data after the IEND chunk without warning
+    #[structopt(long, conflicts_with = "strip-trailing")]
+    pub keep_trailing: bool,
+
+    /// Remove any data after the IEND chunk
+    #[structopt(long)]
+    pub strip_trailing: bool,
+}
+
+/// Where a decoded, removed or dumped payload is written
+#[derive(Debug, Clone, StructOpt)]
+pub struct PayloadArgs {
+    /// Write the payload to this file instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    pub out: Option<PathBuf>,
+
+    /// Write a binary payload to stdout even when it's a terminal
+    #[structopt(long)]
+    pub raw: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RemoveArgs {
+    /// PNG file to remove the chunk from
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Type of the chunk to remove. Only the first chunk of this type is removed
+    #[structopt(required_unless_one = &["all-private", "ancillary", "larger-than"])]
+    pub chunk_type: Option<ChunkType>,
+
+    /// Remove every private chunk, rather than one chunk of a given type
+    #[structopt(long, conflicts_with = "chunk-type")]
+    pub all_private: bool,
+
+    /// Remove every ancillary chunk, rather than one chunk of a given type
+    #[structopt(long, conflicts_with = "chunk-type")]
+    pub ancillary: bool,
+
+    /// Remove every chunk with more data than this, e.g. 1M, rather than one chunk of a given type
+    #[structopt(long, parse(try_from_str = byte_size), conflicts_with = "chunk-type")]
+    pub larger_than: Option<usize>,
+
+    #[structopt(flatten)]
+    pub payload: PayloadArgs,
+
+    #[structopt(flatten)]
+    pub trailing: TrailingArgs,
+
+    /// Fail without writing if the decoded image data would change
+    #[structopt(long)]
+    pub verify_pixels: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExtractArgs {
+    /// PNG file to extract from
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Write any data appended after the IEND chunk to this file
+    #[structopt(long, parse(from_os_str), required_unless = "xmp")]
+    pub trailing: Option<PathBuf>,
+
+    /// Print the file's XMP metadata packet, indented for reading
+    #[structopt(long)]
+    pub xmp: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct PrintArgs {
+    /// PNG file to list
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Show the entropy of each chunk, flagging ancillary chunks which look compressed or
+    /// encrypted
+    #[structopt(long)]
+    pub entropy: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct DumpArgs {
+    /// PNG file to dump the chunk from
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Type of the chunk to dump. The first one is used if there are several
+    pub chunk_type: ChunkType,
+
+    #[structopt(flatten)]
+    pub payload: PayloadArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct InjectArgs {
+    /// PNG file to insert the chunk into
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// File holding exactly one ancillary chunk, including its length, type and CRC
+    #[structopt(long, parse(from_os_str))]
+    pub chunk: PathBuf,
+
+    /// Index to insert the chunk at, from 1 (just after IHDR). By default it goes just before IEND
+    #[structopt(long)]
+    pub position: Option<usize>,
+
+    #[structopt(flatten)]
+    pub trailing: TrailingArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MoveArgs {
+    /// PNG file to rearrange
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Type of the chunk to move. The first one is moved if there are several
+    pub chunk_type: ChunkType,
+
+    /// Index the chunk ends up at, from 1 (just after IHDR)
+    #[structopt(long)]
+    pub position: usize,
+
+    #[structopt(flatten)]
+    pub trailing: TrailingArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct FramesArgs {
+    /// Animated PNG file
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Directory to write each frame to as a standalone PNG file, created if it doesn't exist.
+    /// Without this the frames are listed instead
+    #[structopt(long, parse(from_os_str))]
+    pub extract: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum IccArgs {
+    /// Write the file's ICC profile, uncompressed, to another file
+    Extract {
+        /// PNG file to read the profile from
+        #[structopt(parse(from_os_str))]
+        file_path: PathBuf,
+
+        /// Where to write the profile
+        #[structopt(parse(from_os_str))]
+        output_file: PathBuf,
+    },
+
+    /// Embed an ICC profile, replacing any existing profile or sRGB chunk
+    Insert {
+        /// PNG file to embed the profile in
+        #[structopt(parse(from_os_str))]
+        file_path: PathBuf,
+
+        /// ICC profile to embed
+        #[structopt(parse(from_os_str))]
+        profile_file: PathBuf,
+
+        /// Name stored alongside the profile, 1-79 Latin-1 characters
+        #[structopt(long, default_value = "ICC Profile")]
+        name: String,
+
+        #[structopt(flatten)]
+        trailing: TrailingArgs,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum AnnotateArgs {
+    /// Add an annotation, replacing any existing value for the key
+    Set {
+        /// PNG file to annotate
+        #[structopt(parse(from_os_str))]
+        file_path: PathBuf,
+
+        /// Annotation key, e.g. case-number
+        key: String,
+
+        /// Annotation value
+        value: String,
+
+        #[structopt(flatten)]
+        trailing: TrailingArgs,
+    },
+
+    /// Print the value of an annotation
+    Get {
+        /// PNG file to read the annotation from
+        #[structopt(parse(from_os_str))]
+        file_path: PathBuf,
+
+        /// Annotation key
+        key: String,
+    },
+
+    /// List the file's annotations
+    List {
+        /// PNG file to read the annotations from
+        #[structopt(parse(from_os_str))]
+        file_path: PathBuf,
+
+        /// Only list annotations whose key starts with this prefix
+        #[structopt(long, default_value = "")]
+        prefix: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct StripArgs {
+    /// PNG file to strip
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Which chunks to remove: identifying metadata (privacy), anything browsers ignore (web)
+    /// or everything not needed to decode the image (minimal)
+    #[structopt(long, possible_values = &["privacy", "web", "minimal"])]
+    pub preset: StripPreset,
+
+    #[structopt(flatten)]
+    pub trailing: TrailingArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AnonymizeArgs {
+    /// PNG file to anonymize
+    #[structopt(parse(from_os_str))]
+    pub file_path: PathBuf,
+
+    /// Where to write the result, instead of overwriting the input file
+    #[structopt(parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Fail without writing if the decoded image data would change
+    #[structopt(long)]
+    pub verify_pixels: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct NewArgs {
+    /// Image size as <width>x<height>, e.g. 640x480. Only optional with --fit-payload
+    #[structopt(parse(try_from_str = dimensions), required_unless = "fit-payload")]
+    pub size: Option<(u32, u32)>,
+
+    /// Fill with a single colour given as hex RGB, e.g. ff8000. White if no fill is given
+    #[structopt(long, parse(try_from_str = hex_color), conflicts_with_all = &["noise", "gradient"])]
+    pub color: Option<[u8; 3]>,
+
+    /// Fill with random noise, so the image data has high entropy. Give a seed to get the same
+    /// image every time
+    #[structopt(long, alias = "seed", conflicts_with = "gradient")]
+    pub noise: Option<Option<u64>>,
+
+    /// Fill with a smooth gradient, which compresses like a photo
+    #[structopt(long)]
+    pub gradient: bool,
+
+    /// Pick the size so that the image data is at least as large as this file, so the payload
+    /// doesn't dwarf the image once it's encoded. Overrides the size if both are given
+    #[structopt(long, parse(from_os_str))]
+    pub fit_payload: Option<PathBuf>,
+
+    /// Where to write the image
+    #[structopt(long, parse(from_os_str))]
+    pub out: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SelftestArgs {
+    /// PNG file to test with, which isn't modified. A generated image is used if not given
+    #[structopt(parse(from_os_str))]
+    pub file_path: Option<PathBuf>,
+}
+
+/// Parse a size in bytes, with an optional K, M or G suffix for powers of 1024, e.g. 64K or 64KB
+fn byte_size(value: &str) -> Result<usize, String> {
+    let invalid = || format!("'{}' isn't a size in bytes, e.g. 4096 or 64K", value);
+    let unit = value
+        .strip_suffix(|c| c == 'B' || c == 'b')
+        .filter(|rest| rest.ends_with(|c: char| "KkMmGg".contains(c)))
+        .unwrap_or(value);
+    let (digits, multiplier) = match unit.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&unit[..i], 1 << 10),
+        Some((i, 'M')) | Some((i, 'm')) => (&unit[..i], 1 << 20),
+        Some((i, 'G')) | Some((i, 'g')) => (&unit[..i], 1 << 30),
+        _ => (unit, 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(invalid)
+}
+
+/// Parse image dimensions given as <width>x<height>
+fn dimensions(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("'{}' isn't of the form <width>x<height>", value);
+    let mut parts = value.splitn(2, 'x');
+    let width = parts
+        .next()
+        .and_then(|w| w.parse().ok())
+        .ok_or_else(invalid)?;
+    let height = parts
+        .next()
+        .and_then(|h| h.parse().ok())
+        .ok_or_else(invalid)?;
+    Ok((width, height))
+}
+
+/// Parse a colour given as six hex digits, with or without a leading '#'
+fn hex_color(value: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("'{}' isn't a hex RGB colour, e.g. ff8000", value);
+    let digits = value.trim_start_matches('#');
+    if digits.len() != 6 {
+        return Err(invalid());
+    }
+
+    let channel = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|d| u8::from_str_radix(d, 16).ok())
+            .ok_or_else(invalid)
+    };
+
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Reject arguments which aren't valid UTF-8 with an error, rather than the panic structopt
+/// gives for a plain String
+fn utf8_argument(value: &OsStr) -> Result<String, OsString> {
+    value
+        .to_str()
+        .map(String::from)
+        .ok_or_else(|| OsString::from("the message must be valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!(byte_size("4096"), Ok(4096));
+        assert_eq!(byte_size("64K"), Ok(64 * 1024));
+        assert_eq!(byte_size("2m"), Ok(2 * 1024 * 1024));
+        assert_eq!(byte_size("1G"), Ok(1024 * 1024 * 1024));
+        assert_eq!(byte_size("1MB"), Ok(1024 * 1024));
+        assert_eq!(byte_size("8kb"), Ok(8 * 1024));
+        assert!(byte_size("").is_err());
+        assert!(byte_size("K").is_err());
+        assert!(byte_size("1.5M").is_err());
+        assert!(byte_size("1T").is_err());
+        assert!(byte_size("10B").is_err());
+    }
+
+    #[test]
+    fn test_size_budget() {
+        assert_eq!(SizeBudget::from_str("4K"), Ok(SizeBudget::Bytes(4096)));
+        assert_eq!(SizeBudget::from_str("2.5%"), Ok(SizeBudget::Percent(2.5)));
+        assert!(SizeBudget::from_str("-1%").is_err());
+        assert!(SizeBudget::from_str("lots").is_err());
+
+        assert!(SizeBudget::Bytes(10).allows(100, 110));
+        assert!(!SizeBudget::Bytes(10).allows(100, 111));
+        assert!(SizeBudget::Percent(2.0).allows(1000, 1020));
+        assert!(!SizeBudget::Percent(2.0).allows(1000, 1021));
+        assert!(SizeBudget::Percent(0.0).allows(1000, 900));
+    }
+
+    #[test]
+    fn test_assert_max_growth_alias() {
+        let args = PngMeArgs::from_iter(&[
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "message",
+            "--assert-max-growth",
+            "2%",
+        ]);
+
+        match args.command {
+            Command::Encode(args) => {
+                assert_eq!(args.max_overhead, Some(SizeBudget::Percent(2.0)))
+            }
+            _ => panic!("expected encode"),
+        }
+    }
+
+    #[test]
+    fn test_external_subcommand() {
+        let args = PngMeArgs::from_iter(&["pngme", "foo", "--bar", "baz"]);
+
+        match args.command {
+            Command::External(args) => assert_eq!(args, vec!["foo", "--bar", "baz"]),
+            _ => panic!("expected an external command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let args = PngMeArgs::from_iter(&[
+            "pngme",
+            "decode",
+            "image.png",
+            "ruSt",
+            "--max-file-size",
+            "1M",
+            "--max-chunks",
+            "10",
+        ]);
+        let options = args.parse.options();
+
+        assert_eq!(options.max_file_size, 1024 * 1024);
+        assert_eq!(options.max_chunks, 10);
+        assert_eq!(
+            options.max_chunk_length,
+            ParseOptions::DEFAULT_MAX_CHUNK_LENGTH
+        );
+    }
+}
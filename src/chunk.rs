@@ -1,12 +1,14 @@
 use crate::chunk_type::ChunkType;
+use crate::parse_options::ParseOptions;
 use crate::{Error, Result};
+use crc::{crc32, Hasher32};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::Display,
 };
 
 /// Represents a single chunk in the PNG spec
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Chunk {
     chunk_type: ChunkType,
     data: Vec<u8>,
@@ -37,20 +39,51 @@ impl Chunk {
     }
 
     /// Chunk data
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
     /// CRC of the entire chunk
-    fn crc(&self) -> u32 {
-        let bytes: Vec<u8> = self
-            .chunk_type
-            .bytes()
+    pub fn crc(&self) -> u32 {
+        let mut digest = crc32::Digest::new(crc32::IEEE);
+        digest.write(&self.chunk_type.bytes());
+        digest.write(&self.data);
+        digest.sum32()
+    }
+
+    /// Shannon entropy of the chunk data in bits per byte, from 0 (constant) to 8 (uniformly
+    /// random). Text sits well below compressed or encrypted data
+    pub fn entropy(&self) -> f64 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
+
+        let mut counts = [0usize; 256];
+        for &b in &self.data {
+            counts[b as usize] += 1;
+        }
+
+        let length = self.data.len() as f64;
+        counts
             .iter()
-            .chain(self.data.iter())
-            .copied()
-            .collect();
-        crc::crc32::checksum_ieee(&bytes)
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / length;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Entropy as a fraction of the most that's possible for data of this length. Short data
+    /// can't reach 8 bits per byte, as n bytes can hold at most n distinct values
+    pub fn relative_entropy(&self) -> f64 {
+        let max = (self.data.len().min(256) as f64).log2();
+
+        if max == 0.0 {
+            return 0.0;
+        }
+
+        self.entropy() / max
     }
 
     /// Chunk data as a string
@@ -61,22 +94,23 @@ impl Chunk {
 
     /// Entire chunk represented as bytes
     pub fn as_bytes(&self) -> Vec<u8> {
-        let data_length = self.data.len() as u32;
-        data_length
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.data.iter())
-            .chain(self.crc().to_be_bytes().iter())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.data.len() + Chunk::METADATA_BYTES);
+        self.write_to(&mut bytes);
+        bytes
     }
-}
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    /// Append the entire chunk to a buffer, so that a whole file can be serialized without an
+    /// intermediate allocation per chunk
+    pub fn write_to(&self, buffer: &mut Vec<u8>) {
+        let data_length = self.data.len() as u32;
+        buffer.extend_from_slice(&data_length.to_be_bytes());
+        buffer.extend_from_slice(&self.chunk_type.bytes());
+        buffer.extend_from_slice(&self.data);
+        buffer.extend_from_slice(&self.crc().to_be_bytes());
+    }
 
-    fn try_from(value: &[u8]) -> Result<Self> {
+    /// Parse a chunk from the start of the given bytes, enforcing the given limits
+    pub fn parse_with_options(value: &[u8], options: &ParseOptions) -> Result<Self> {
         if value.len() < Chunk::METADATA_BYTES {
             return Err(Box::from(ChunkError::InputTooSmall));
         }
@@ -85,6 +119,13 @@ impl TryFrom<&[u8]> for Chunk {
         let (data_length, value) = value.split_at(Chunk::DATA_LENGTH_BYTES);
         let data_length = u32::from_be_bytes(data_length.try_into()?) as usize;
 
+        if data_length > options.max_chunk_length {
+            return Err(Box::from(ChunkError::TooLarge(
+                data_length,
+                options.max_chunk_length,
+            )));
+        }
+
         // consume next 4 bytes as chunk type
         let (chunk_type_bytes, value) = value.split_at(Chunk::CHUNK_TYPE_BYTES);
 
@@ -95,6 +136,15 @@ impl TryFrom<&[u8]> for Chunk {
             return Err(Box::from(ChunkError::InvalidChunkType));
         }
 
+        // the declared length comes straight from the file, so it can't be trusted to fit
+        let needed = data_length.saturating_add(Chunk::CRC_BYTES);
+        if needed > value.len() {
+            return Err(Box::from(ChunkError::Truncated {
+                needed,
+                available: value.len(),
+            }));
+        }
+
         let (data, value) = value.split_at(data_length);
         let (crc_bytes, _) = value.split_at(Chunk::CRC_BYTES);
 
@@ -107,12 +157,50 @@ impl TryFrom<&[u8]> for Chunk {
         let actual_crc = new.crc();
         let expected_crc = u32::from_be_bytes(crc_bytes.try_into()?);
 
-        if expected_crc != actual_crc {
+        let placeholder_crc = options.lenient_crc && expected_crc == 0;
+
+        if expected_crc != actual_crc && !placeholder_crc {
             return Err(Box::from(ChunkError::InvalidCrc(expected_crc, actual_crc)));
         }
 
         Ok(new)
     }
+
+    /// Salvage a chunk which runs past the end of the input, keeping whatever data is available.
+    /// There's no CRC to check, but the chunk type must still be valid
+    pub fn parse_truncated(value: &[u8]) -> Result<Self> {
+        let metadata_length = Chunk::DATA_LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES;
+
+        if value.len() < metadata_length {
+            return Err(Box::from(ChunkError::InputTooSmall));
+        }
+
+        let (data_length, value) = value.split_at(Chunk::DATA_LENGTH_BYTES);
+        let data_length = u32::from_be_bytes(data_length.try_into()?) as usize;
+
+        let (chunk_type_bytes, value) = value.split_at(Chunk::CHUNK_TYPE_BYTES);
+        let chunk_type_bytes: [u8; 4] = chunk_type_bytes.try_into()?;
+        let chunk_type = ChunkType::try_from(chunk_type_bytes)?;
+
+        if !chunk_type.is_valid() {
+            return Err(Box::from(ChunkError::InvalidChunkType));
+        }
+
+        let data = &value[..data_length.min(value.len())];
+
+        Ok(Self {
+            chunk_type,
+            data: data.into(),
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Chunk {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Chunk::parse_with_options(value, &ParseOptions::default())
+    }
 }
 
 #[derive(Debug)]
@@ -120,6 +208,15 @@ pub enum ChunkError {
     InputTooSmall,
     InvalidCrc(u32, u32),
     InvalidChunkType,
+
+    /// Declared data length exceeds the configured maximum
+    TooLarge(usize, usize),
+
+    /// Declared data length runs past the end of the input. Counts exclude the length and type
+    Truncated {
+        needed: usize,
+        available: usize,
+    },
 }
 
 impl std::error::Error for ChunkError {}
@@ -136,6 +233,16 @@ impl Display for ChunkError {
                 expected, actual
             ),
             ChunkError::InvalidChunkType => write!(f, "Invalid chunk type"),
+            ChunkError::TooLarge(length, max) => write!(
+                f,
+                "Chunk declares {} bytes of data but at most {} are allowed",
+                length, max
+            ),
+            ChunkError::Truncated { needed, available } => write!(
+                f,
+                "Chunk is truncated. Expected {} more bytes of data and CRC but only {} remain",
+                needed, available
+            ),
         }
     }
 }
@@ -180,6 +287,37 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_entropy() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+
+        assert_eq!(Chunk::new(chunk_type.clone(), Vec::new()).entropy(), 0.0);
+        assert_eq!(Chunk::new(chunk_type.clone(), vec![7; 100]).entropy(), 0.0);
+        assert_eq!(Chunk::new(chunk_type, (0..=255).collect()).entropy(), 8.0);
+
+        let text = testing_chunk().entropy();
+        assert!(text > 3.0 && text < 5.0);
+    }
+
+    #[test]
+    fn test_chunk_relative_entropy() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+
+        assert_eq!(
+            Chunk::new(chunk_type.clone(), vec![7]).relative_entropy(),
+            0.0
+        );
+        assert_eq!(
+            Chunk::new(chunk_type.clone(), (0..64).collect()).entropy(),
+            6.0
+        );
+        assert_eq!(
+            Chunk::new(chunk_type, (0..64).collect()).relative_entropy(),
+            1.0
+        );
+        assert!(testing_chunk().relative_entropy() < 0.75);
+    }
+
     #[test]
     fn test_as_bytes() {
         let data_length: u32 = 42;
@@ -251,4 +389,72 @@ mod tests {
 
         assert!(chunk.is_err());
     }
+
+    #[test]
+    fn test_chunk_length_beyond_input() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        chunk_data[..Chunk::DATA_LENGTH_BYTES].copy_from_slice(&1000u32.to_be_bytes());
+
+        let error = Chunk::try_from(chunk_data.as_ref()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ChunkError>(),
+            Some(ChunkError::Truncated {
+                needed: 1004,
+                available: 46
+            })
+        ));
+
+        let error = Chunk::try_from(&testing_chunk().as_bytes()[..20]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ChunkError>(),
+            Some(ChunkError::Truncated {
+                needed: 46,
+                available: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let chunk_data = testing_chunk().as_bytes();
+
+        let chunk = Chunk::parse_truncated(&chunk_data[..20]).unwrap();
+        assert_eq!(chunk.chunk_type(), testing_chunk().chunk_type());
+        assert_eq!(chunk.data(), &testing_chunk().data()[..12]);
+
+        let chunk = Chunk::parse_truncated(&chunk_data[..52]).unwrap();
+        assert_eq!(chunk, testing_chunk());
+
+        assert!(Chunk::parse_truncated(&chunk_data[..6]).is_err());
+    }
+
+    #[test]
+    fn test_chunk_exceeding_max_length() {
+        let chunk = testing_chunk();
+        let options = ParseOptions {
+            max_chunk_length: 41,
+            ..ParseOptions::default()
+        };
+
+        let result = Chunk::parse_with_options(&chunk.as_bytes(), &options);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_placeholder_crc() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let crc_start = chunk_data.len() - Chunk::CRC_BYTES;
+        chunk_data[crc_start..].copy_from_slice(&[0, 0, 0, 0]);
+
+        assert!(Chunk::try_from(chunk_data.as_ref()).is_err());
+
+        let options = ParseOptions {
+            lenient_crc: true,
+            ..ParseOptions::default()
+        };
+        let chunk = Chunk::parse_with_options(&chunk_data, &options).unwrap();
+
+        assert_eq!(chunk.as_bytes(), testing_chunk().as_bytes());
+    }
 }
@@ -0,0 +1,144 @@
+mod apng;
+mod bkgd;
+mod hist;
+mod iccp;
+mod ihdr;
+mod itxt;
+mod plte;
+mod splt;
+mod trns;
+
+pub use apng::{Animation, AnimationControl, BlendOp, DisposeOp, Frame, FrameControl};
+pub use bkgd::Background;
+pub use hist::Histogram;
+pub use iccp::IccProfile;
+pub use ihdr::{ColorType, ImageHeader};
+pub use itxt::InternationalText;
+pub use plte::Palette;
+pub use splt::{SuggestedPalette, SuggestedPaletteEntry};
+pub use trns::Transparency;
+
+use crate::chunk::Chunk;
+use crate::parse_options::ParseOptions;
+use crate::Result;
+use flate2::read::ZlibDecoder;
+use std::{convert::TryInto, fmt::Display, io::Read};
+
+/// Check that a chunk is of the expected type before interpreting its data
+fn expect_chunk_type(
+    chunk: &Chunk,
+    expected: &'static str,
+) -> std::result::Result<(), ChunkDataError> {
+    if !chunk.chunk_type().matches(expected) {
+        let actual = chunk.chunk_type().to_string();
+        return Err(ChunkDataError::WrongChunkType(expected, actual));
+    }
+
+    Ok(())
+}
+
+/// Read a big-endian u32 at the given offset. The caller must have checked the length
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read a big-endian u16 at the given offset. The caller must have checked the length
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+/// Longest keyword (e.g. palette or profile name) allowed by the spec
+const MAX_KEYWORD_LENGTH: usize = 79;
+
+/// Read a NUL-terminated Latin-1 keyword from the start of the data, returning it along with the
+/// number of bytes consumed including the terminator
+fn read_keyword(data: &[u8]) -> Option<(String, usize)> {
+    let end = data
+        .iter()
+        .position(|&b| b == 0)
+        .filter(|&end| end > 0 && end <= MAX_KEYWORD_LENGTH)?;
+
+    // Latin-1 maps directly onto the first 256 code points
+    Some((data[..end].iter().map(|&b| b as char).collect(), end + 1))
+}
+
+/// Encode a keyword as NUL-terminated Latin-1, if it's valid
+fn keyword_bytes(keyword: &str) -> Option<Vec<u8>> {
+    if keyword.is_empty()
+        || keyword.chars().count() > MAX_KEYWORD_LENGTH
+        || keyword.chars().any(|c| c == '\0' || c as u32 > 0xFF)
+    {
+        return None;
+    }
+
+    Some(
+        keyword
+            .chars()
+            .map(|c| c as u8)
+            .chain(std::iter::once(0))
+            .collect(),
+    )
+}
+
+/// Inflate zlib-compressed chunk data, stopping as soon as the output exceeds the size or ratio
+/// limits rather than decompressing everything and checking afterwards
+fn inflate(chunk_type: &'static str, data: &[u8], options: &ParseOptions) -> Result<Vec<u8>> {
+    let limit = options
+        .max_inflated_length
+        .min(data.len().saturating_mul(options.max_inflate_ratio));
+
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut inflated)?;
+
+    if inflated.len() > limit {
+        return Err(Box::from(ChunkDataError::InflateLimit(chunk_type, limit)));
+    }
+
+    Ok(inflated)
+}
+
+/// Whether a sample value can be represented at the given bit depth
+fn fits_bit_depth(sample: u16, bit_depth: u8) -> bool {
+    bit_depth >= 16 || u32::from(sample) < 1 << bit_depth
+}
+
+/// Errors interpreting the data of a known chunk type
+#[derive(Debug)]
+pub enum ChunkDataError {
+    /// Expected a chunk of the first type but found the second
+    WrongChunkType(&'static str, String),
+
+    /// Chunk of the given type has a data length which isn't allowed
+    InvalidLength(&'static str, usize),
+
+    /// Chunk of the given type has an invalid value in the given field
+    InvalidValue(&'static str, &'static str),
+
+    /// Compressed data in a chunk of the given type inflates to more than the given limit
+    InflateLimit(&'static str, usize),
+}
+
+impl std::error::Error for ChunkDataError {}
+
+impl Display for ChunkDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkDataError::WrongChunkType(expected, actual) => {
+                write!(f, "Expected a {} chunk but found {}", expected, actual)
+            }
+            ChunkDataError::InvalidLength(chunk_type, length) => {
+                write!(f, "Invalid {} chunk length {}", chunk_type, length)
+            }
+            ChunkDataError::InvalidValue(chunk_type, field) => {
+                write!(f, "Invalid {} in {} chunk", field, chunk_type)
+            }
+            ChunkDataError::InflateLimit(chunk_type, limit) => write!(
+                f,
+                "Compressed data in {} chunk inflates to more than {} bytes",
+                chunk_type, limit
+            ),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use super::{expect_chunk_type, read_u16, read_u32, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::{Error, Result};
+use std::convert::TryFrom;
+
+/// Animation control (acTL) chunk, which marks a PNG as animated
+///
+/// See [APNG - acTL](https://wiki.mozilla.org/APNG_Specification#.60acTL.60:_The_Animation_Control_Chunk) for details
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AnimationControl {
+    /// Number of frames in the animation
+    pub num_frames: u32,
+
+    /// Number of times to loop the animation, 0 meaning forever
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub const CHUNK_TYPE: &'static str = "acTL";
+    const LENGTH: usize = 8;
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, AnimationControl::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        if data.len() != AnimationControl::LENGTH {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                AnimationControl::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        Ok(Self {
+            num_frames: read_u32(data, 0),
+            num_plays: read_u32(data, 4),
+        })
+    }
+}
+
+/// How the frame area is disposed of before rendering the next frame
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+/// How the frame is rendered onto the output buffer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+/// Frame control (fcTL) chunk, which describes the position, timing and composition of the
+/// frame whose image data follows it
+///
+/// See [APNG - fcTL](https://wiki.mozilla.org/APNG_Specification#.60fcTL.60:_The_Frame_Control_Chunk) for details
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FrameControl {
+    /// Position of this chunk in the animation's sequence of fcTL and fdAT chunks
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+
+    /// Numerator of the frame delay, in seconds
+    pub delay_num: u16,
+
+    /// Denominator of the frame delay, 0 meaning 100
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub const CHUNK_TYPE: &'static str = "fcTL";
+    const LENGTH: usize = 26;
+
+    /// Time to display the frame, in seconds
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        f64::from(self.delay_num) / f64::from(den)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, FrameControl::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        if data.len() != FrameControl::LENGTH {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                FrameControl::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            _ => {
+                return Err(Box::from(ChunkDataError::InvalidValue(
+                    FrameControl::CHUNK_TYPE,
+                    "dispose op",
+                )))
+            }
+        };
+
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            _ => {
+                return Err(Box::from(ChunkDataError::InvalidValue(
+                    FrameControl::CHUNK_TYPE,
+                    "blend op",
+                )))
+            }
+        };
+
+        Ok(Self {
+            sequence_number: read_u32(data, 0),
+            width: read_u32(data, 4),
+            height: read_u32(data, 8),
+            x_offset: read_u32(data, 12),
+            y_offset: read_u32(data, 16),
+            delay_num: read_u16(data, 20),
+            delay_den: read_u16(data, 22),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+/// A single frame of an animation, with the indexes of the IDAT or fdAT chunks holding its image
+/// data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub data_chunks: Vec<usize>,
+}
+
+/// The animation described by an APNG's acTL, fcTL and fdAT chunks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub const FRAME_DATA_CHUNK_TYPE: &'static str = "fdAT";
+
+    /// Interpret the animation chunks of a PNG, if it has any
+    pub fn from_chunks(chunks: &[Chunk]) -> Result<Option<Self>> {
+        let control = match chunks
+            .iter()
+            .find(|c| c.chunk_type().matches(AnimationControl::CHUNK_TYPE))
+        {
+            Some(chunk) => AnimationControl::try_from(chunk)?,
+            None => return Ok(None),
+        };
+
+        let mut frames: Vec<Frame> = Vec::new();
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let chunk_type = chunk.chunk_type();
+
+            if chunk_type.matches(FrameControl::CHUNK_TYPE) {
+                frames.push(Frame {
+                    control: FrameControl::try_from(chunk)?,
+                    data_chunks: Vec::new(),
+                });
+            } else if chunk_type.matches("IDAT")
+                || chunk_type.matches(Animation::FRAME_DATA_CHUNK_TYPE)
+            {
+                // IDAT before the first fcTL is a default image which isn't part of the animation
+                if let Some(frame) = frames.last_mut() {
+                    frame.data_chunks.push(index);
+                }
+            }
+        }
+
+        Ok(Some(Self { control, frames }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[rustfmt::skip]
+    const FRAME_CONTROL: [u8; 26] = [
+        0, 0, 0, 3,     // sequence number
+        0, 0, 0, 32,    // width
+        0, 0, 0, 16,    // height
+        0, 0, 0, 4,     // x offset
+        0, 0, 0, 2,     // y offset
+        0, 1, 0, 25,    // delay 1/25s
+        1, 1,           // dispose to background, blend over
+    ];
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_animation_control() {
+        let actl = AnimationControl::try_from(&chunk("acTL", &[0, 0, 0, 5, 0, 0, 0, 0])).unwrap();
+        assert_eq!(actl.num_frames, 5);
+        assert_eq!(actl.num_plays, 0);
+    }
+
+    #[test]
+    fn test_animation_control_invalid_length() {
+        assert!(AnimationControl::try_from(&chunk("acTL", &[0, 0, 0, 5])).is_err());
+    }
+
+    #[test]
+    fn test_frame_control() {
+        let fctl = FrameControl::try_from(&chunk("fcTL", &FRAME_CONTROL)).unwrap();
+
+        assert_eq!(fctl.sequence_number, 3);
+        assert_eq!((fctl.width, fctl.height), (32, 16));
+        assert_eq!((fctl.x_offset, fctl.y_offset), (4, 2));
+        assert!((fctl.delay() - 0.04).abs() < f64::EPSILON);
+        assert_eq!(fctl.dispose_op, DisposeOp::Background);
+        assert_eq!(fctl.blend_op, BlendOp::Over);
+    }
+
+    #[test]
+    fn test_frame_control_default_delay_denominator() {
+        let mut data = FRAME_CONTROL;
+        data[22..24].copy_from_slice(&[0, 0]);
+
+        let fctl = FrameControl::try_from(&chunk("fcTL", &data)).unwrap();
+
+        assert!((fctl.delay() - 0.01).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_frame_control_invalid_dispose_op() {
+        let mut data = FRAME_CONTROL;
+        data[24] = 3;
+        assert!(FrameControl::try_from(&chunk("fcTL", &data)).is_err());
+    }
+
+    #[test]
+    fn test_animation_from_chunks() {
+        let mut second_frame = FRAME_CONTROL;
+        second_frame[3] = 1;
+
+        let chunks = vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("acTL", &[0, 0, 0, 2, 0, 0, 0, 0]),
+            chunk("fcTL", &FRAME_CONTROL),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IDAT", &[4, 5, 6]),
+            chunk("fcTL", &second_frame),
+            chunk("fdAT", &[0, 0, 0, 2, 7, 8, 9]),
+            chunk("IEND", &[]),
+        ];
+
+        let animation = Animation::from_chunks(&chunks).unwrap().unwrap();
+
+        assert_eq!(animation.control.num_frames, 2);
+        assert_eq!(animation.frames.len(), 2);
+        assert_eq!(animation.frames[0].data_chunks, vec![3, 4]);
+        assert_eq!(animation.frames[1].control.sequence_number, 1);
+        assert_eq!(animation.frames[1].data_chunks, vec![6]);
+    }
+
+    #[test]
+    fn test_default_image_is_not_a_frame() {
+        let chunks = vec![
+            chunk("acTL", &[0, 0, 0, 1, 0, 0, 0, 0]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("fcTL", &FRAME_CONTROL),
+            chunk("fdAT", &[0, 0, 0, 4, 7, 8, 9]),
+        ];
+
+        let animation = Animation::from_chunks(&chunks).unwrap().unwrap();
+
+        assert_eq!(animation.frames.len(), 1);
+        assert_eq!(animation.frames[0].data_chunks, vec![3]);
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let chunks = vec![chunk("IDAT", &[1, 2, 3]), chunk("IEND", &[])];
+        assert_eq!(Animation::from_chunks(&chunks).unwrap(), None);
+    }
+
+    #[test]
+    fn test_wrong_chunk_type() {
+        assert!(FrameControl::try_from(&chunk("acTL", &FRAME_CONTROL)).is_err());
+    }
+}
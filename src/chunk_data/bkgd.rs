@@ -0,0 +1,172 @@
+use super::{
+    expect_chunk_type, fits_bit_depth, read_u16, ChunkDataError, ColorType, ImageHeader, Palette,
+};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::Result;
+use std::str::FromStr;
+
+/// Background colour (bKGD) chunk, whose format depends on the image's colour type
+///
+/// See [PNG Chunks - bKGD](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.bKGD) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Background {
+    /// Grey level for greyscale images, with or without alpha
+    Gray(u16),
+
+    /// Colour for truecolour images, with or without alpha
+    Rgb(u16, u16, u16),
+
+    /// Palette entry for indexed colour images
+    PaletteIndex(u8),
+}
+
+impl Background {
+    pub const CHUNK_TYPE: &'static str = "bKGD";
+
+    /// Interpret a bKGD chunk for an image of the given colour type
+    pub fn parse(chunk: &Chunk, color_type: ColorType) -> Result<Self> {
+        expect_chunk_type(chunk, Background::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        let background = match (color_type, data.len()) {
+            (ColorType::Grayscale, 2) | (ColorType::GrayscaleAlpha, 2) => {
+                Background::Gray(read_u16(data, 0))
+            }
+            (ColorType::Rgb, 6) | (ColorType::Rgba, 6) => {
+                Background::Rgb(read_u16(data, 0), read_u16(data, 2), read_u16(data, 4))
+            }
+            (ColorType::Indexed, 1) => Background::PaletteIndex(data[0]),
+            (_, length) => {
+                return Err(Box::from(ChunkDataError::InvalidLength(
+                    Background::CHUNK_TYPE,
+                    length,
+                )))
+            }
+        };
+
+        Ok(background)
+    }
+
+    /// Check the background matches the image's colour type and bit depth, and refers to an
+    /// existing palette entry for indexed colour images
+    pub fn validate(&self, header: &ImageHeader, palette: Option<&Palette>) -> Result<()> {
+        let valid = match (self, header.color_type) {
+            (Background::Gray(gray), ColorType::Grayscale)
+            | (Background::Gray(gray), ColorType::GrayscaleAlpha) => {
+                fits_bit_depth(*gray, header.bit_depth)
+            }
+            (Background::Rgb(r, g, b), ColorType::Rgb)
+            | (Background::Rgb(r, g, b), ColorType::Rgba) => [r, g, b]
+                .iter()
+                .all(|&&sample| fits_bit_depth(sample, header.bit_depth)),
+            (Background::PaletteIndex(index), ColorType::Indexed) => {
+                palette.is_some_and(|p| usize::from(*index) < p.entries.len())
+            }
+            _ => false,
+        };
+
+        if !valid {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                Background::CHUNK_TYPE,
+                "background colour",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Encode the background as a chunk
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Background::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Background::Rgb(r, g, b) => [r, g, b]
+                .iter()
+                .flat_map(|s| s.to_be_bytes().to_vec())
+                .collect(),
+            Background::PaletteIndex(index) => vec![*index],
+        };
+
+        let chunk_type = ChunkType::from_str(Background::CHUNK_TYPE).expect("bKGD is valid");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("bKGD").unwrap(), data.to_vec())
+    }
+
+    fn header(color_type: ColorType, bit_depth: u8) -> ImageHeader {
+        ImageHeader {
+            width: 1,
+            height: 1,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_per_color_type() {
+        assert_eq!(
+            Background::parse(&chunk(&[0, 7]), ColorType::GrayscaleAlpha).unwrap(),
+            Background::Gray(7)
+        );
+        assert_eq!(
+            Background::parse(&chunk(&[0, 1, 0, 2, 1, 0]), ColorType::Rgb).unwrap(),
+            Background::Rgb(1, 2, 256)
+        );
+        assert_eq!(
+            Background::parse(&chunk(&[4]), ColorType::Indexed).unwrap(),
+            Background::PaletteIndex(4)
+        );
+    }
+
+    #[test]
+    fn test_parse_wrong_length_for_color_type() {
+        assert!(Background::parse(&chunk(&[0, 7]), ColorType::Rgb).is_err());
+        assert!(Background::parse(&chunk(&[0, 7]), ColorType::Indexed).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [0, 1, 0, 2, 1, 0];
+        let background = Background::parse(&chunk(&data), ColorType::Rgba).unwrap();
+        assert_eq!(background.to_chunk(), chunk(&data));
+    }
+
+    #[test]
+    fn test_validate_bit_depth() {
+        let header = header(ColorType::Grayscale, 4);
+        assert!(Background::Gray(15).validate(&header, None).is_ok());
+        assert!(Background::Gray(16).validate(&header, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_palette_index() {
+        let header = header(ColorType::Indexed, 8);
+        let palette = Palette {
+            entries: vec![[0, 0, 0]; 2],
+        };
+
+        assert!(Background::PaletteIndex(1)
+            .validate(&header, Some(&palette))
+            .is_ok());
+        assert!(Background::PaletteIndex(2)
+            .validate(&header, Some(&palette))
+            .is_err());
+        assert!(Background::PaletteIndex(0).validate(&header, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_color_type_mismatch() {
+        let header = header(ColorType::Rgb, 8);
+        assert!(Background::Gray(1).validate(&header, None).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use super::{expect_chunk_type, read_u16, ChunkDataError, Palette};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+use std::{convert::TryFrom, str::FromStr};
+
+/// Palette histogram (hIST) chunk, giving the approximate usage frequency of each palette entry
+///
+/// See [PNG Chunks - hIST](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.hIST) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Histogram {
+    pub frequencies: Vec<u16>,
+}
+
+impl Histogram {
+    pub const CHUNK_TYPE: &'static str = "hIST";
+
+    /// A histogram must have exactly one entry per palette entry
+    pub fn validate(&self, palette: &Palette) -> Result<()> {
+        if self.frequencies.len() != palette.entries.len() {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                Histogram::CHUNK_TYPE,
+                self.frequencies.len() * 2,
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Encode the histogram as a chunk
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self
+            .frequencies
+            .iter()
+            .flat_map(|f| f.to_be_bytes().to_vec())
+            .collect();
+
+        let chunk_type = ChunkType::from_str(Histogram::CHUNK_TYPE).expect("hIST is valid");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Histogram {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, Histogram::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        if !data.len().is_multiple_of(2) {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                Histogram::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        let frequencies = (0..data.len())
+            .step_by(2)
+            .map(|i| read_u16(data, i))
+            .collect();
+
+        Ok(Self { frequencies })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("hIST").unwrap(), data.to_vec())
+    }
+
+    fn palette(entries: usize) -> Palette {
+        Palette {
+            entries: vec![[0, 0, 0]; entries],
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::try_from(&chunk(&[0, 5, 1, 0])).unwrap();
+        assert_eq!(histogram.frequencies, vec![5, 256]);
+    }
+
+    #[test]
+    fn test_histogram_round_trip() {
+        let data = [0, 5, 1, 0, 255, 255];
+        let histogram = Histogram::try_from(&chunk(&data)).unwrap();
+        assert_eq!(histogram.to_chunk(), chunk(&data));
+    }
+
+    #[test]
+    fn test_invalid_histogram_length() {
+        assert!(Histogram::try_from(&chunk(&[0, 5, 1])).is_err());
+    }
+
+    #[test]
+    fn test_validate_against_palette() {
+        let histogram = Histogram::try_from(&chunk(&[0, 5, 1, 0])).unwrap();
+
+        assert!(histogram.validate(&palette(2)).is_ok());
+        assert!(histogram.validate(&palette(3)).is_err());
+    }
+}
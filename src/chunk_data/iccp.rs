@@ -0,0 +1,148 @@
+use super::{expect_chunk_type, inflate, keyword_bytes, read_keyword, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::parse_options::ParseOptions;
+use crate::{Error, Result};
+use flate2::{write::ZlibEncoder, Compression};
+use std::{convert::TryFrom, io::Write, str::FromStr};
+
+/// Embedded ICC colour profile (iCCP) chunk
+///
+/// See [PNG Chunks - iCCP](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.iCCP) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IccProfile {
+    /// Profile name, 1-79 Latin-1 characters
+    pub name: String,
+
+    /// Uncompressed ICC profile
+    pub profile: Vec<u8>,
+}
+
+impl IccProfile {
+    pub const CHUNK_TYPE: &'static str = "iCCP";
+
+    /// The only compression method defined by the spec, zlib deflate
+    const COMPRESSION_METHOD: u8 = 0;
+
+    /// Encode the profile as a chunk, compressing the profile data
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.name).ok_or(ChunkDataError::InvalidValue(
+            IccProfile::CHUNK_TYPE,
+            "profile name",
+        ))?;
+        data.push(IccProfile::COMPRESSION_METHOD);
+
+        let mut encoder = ZlibEncoder::new(data, Compression::default());
+        encoder.write_all(&self.profile)?;
+        let data = encoder.finish()?;
+
+        Ok(Chunk::new(
+            ChunkType::from_str(IccProfile::CHUNK_TYPE)?,
+            data,
+        ))
+    }
+
+    /// Decode the profile, enforcing the decompression limits in the given options
+    pub fn parse_with_options(chunk: &Chunk, options: &ParseOptions) -> Result<Self> {
+        expect_chunk_type(chunk, IccProfile::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        let (name, name_length) = read_keyword(data).ok_or(ChunkDataError::InvalidValue(
+            IccProfile::CHUNK_TYPE,
+            "profile name",
+        ))?;
+
+        if data.get(name_length) != Some(&IccProfile::COMPRESSION_METHOD) {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                IccProfile::CHUNK_TYPE,
+                "compression method",
+            )));
+        }
+
+        let profile = inflate(IccProfile::CHUNK_TYPE, &data[name_length + 1..], options)?;
+
+        Ok(Self { name, profile })
+    }
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        IccProfile::parse_with_options(chunk, &ParseOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_profile() -> IccProfile {
+        IccProfile {
+            name: String::from("Display P3"),
+            profile: b"not really an ICC profile".repeat(10),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chunk = testing_profile().to_chunk().unwrap();
+
+        assert!(chunk.length() < testing_profile().profile.len());
+        assert_eq!(IccProfile::try_from(&chunk).unwrap(), testing_profile());
+    }
+
+    #[test]
+    fn test_inflate_limits() {
+        let profile = IccProfile {
+            name: String::from("bomb"),
+            profile: vec![0; 1024 * 1024],
+        };
+        let chunk = profile.to_chunk().unwrap();
+
+        assert!(IccProfile::try_from(&chunk).is_err());
+
+        let options = ParseOptions {
+            max_inflate_ratio: usize::MAX,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            IccProfile::parse_with_options(&chunk, &options).unwrap(),
+            profile
+        );
+
+        let options = ParseOptions {
+            max_inflate_ratio: usize::MAX,
+            max_inflated_length: 1024,
+            ..ParseOptions::default()
+        };
+        assert!(IccProfile::parse_with_options(&chunk, &options).is_err());
+    }
+
+    #[test]
+    fn test_invalid_compression_method() {
+        let mut data = testing_profile().to_chunk().unwrap().data().to_vec();
+        data[11] = 1;
+        let chunk = Chunk::new(ChunkType::from_str("iCCP").unwrap(), data);
+
+        assert!(IccProfile::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_profile_data() {
+        let mut data = b"name\0\0".to_vec();
+        data.extend_from_slice(b"not zlib");
+        let chunk = Chunk::new(ChunkType::from_str("iCCP").unwrap(), data);
+
+        assert!(IccProfile::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let profile = IccProfile {
+            name: String::new(),
+            profile: Vec::new(),
+        };
+        assert!(profile.to_chunk().is_err());
+    }
+}
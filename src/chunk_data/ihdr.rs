@@ -0,0 +1,154 @@
+use super::{expect_chunk_type, read_u32, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+use std::{convert::TryFrom, str::FromStr};
+
+/// Colour types allowed by the PNG spec
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ColorType::Grayscale),
+            2 => Some(ColorType::Rgb),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayscaleAlpha),
+            6 => Some(ColorType::Rgba),
+            _ => None,
+        }
+    }
+
+    fn as_byte(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+/// Image header (IHDR) chunk
+///
+/// See [PNG Chunks - IHDR](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl ImageHeader {
+    pub const CHUNK_TYPE: &'static str = "IHDR";
+    const LENGTH: usize = 13;
+
+    /// Encode the header as a chunk
+    pub fn to_chunk(&self) -> Chunk {
+        let data: Vec<u8> = self
+            .width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(
+                [
+                    self.bit_depth,
+                    self.color_type.as_byte(),
+                    self.compression_method,
+                    self.filter_method,
+                    self.interlace_method,
+                ]
+                .iter(),
+            )
+            .copied()
+            .collect();
+
+        let chunk_type = ChunkType::from_str(ImageHeader::CHUNK_TYPE).expect("IHDR is valid");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, ImageHeader::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        if data.len() != ImageHeader::LENGTH {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                ImageHeader::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        let color_type = ColorType::from_byte(data[9]).ok_or(ChunkDataError::InvalidValue(
+            ImageHeader::CHUNK_TYPE,
+            "colour type",
+        ))?;
+
+        Ok(Self {
+            width: read_u32(data, 0),
+            height: read_u32(data, 4),
+            bit_depth: data[8],
+            color_type,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_chunk() -> Chunk {
+        #[rustfmt::skip]
+        let data = vec![
+            0, 0, 0, 50,    // width
+            0, 0, 0, 40,    // height
+            8, 6,           // bit depth, colour type
+            0, 0, 1,        // compression, filter, interlace
+        ];
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+
+    #[test]
+    fn test_image_header() {
+        let ihdr = ImageHeader::try_from(&testing_chunk()).unwrap();
+
+        assert_eq!((ihdr.width, ihdr.height), (50, 40));
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, 1);
+    }
+
+    #[test]
+    fn test_image_header_round_trip() {
+        let chunk = testing_chunk();
+        let ihdr = ImageHeader::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.to_chunk(), chunk);
+    }
+
+    #[test]
+    fn test_invalid_color_type() {
+        let mut data = testing_chunk().data().to_vec();
+        data[9] = 5;
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), data);
+
+        assert!(ImageHeader::try_from(&chunk).is_err());
+    }
+}
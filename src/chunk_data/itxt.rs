@@ -0,0 +1,241 @@
+use super::{expect_chunk_type, inflate, keyword_bytes, read_keyword, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::parse_options::ParseOptions;
+use crate::{Error, Result};
+use flate2::{write::ZlibEncoder, Compression};
+use std::{convert::TryFrom, io::Write, str::FromStr};
+
+/// International textual data (iTXt) chunk
+///
+/// See [PNG Chunks - iTXt](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.iTXt) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InternationalText {
+    /// Keyword, 1-79 Latin-1 characters
+    pub keyword: String,
+
+    /// Whether the text is stored zlib-compressed
+    pub compressed: bool,
+
+    /// RFC 1766 language tag, empty if unknown
+    pub language_tag: String,
+
+    /// Keyword translated into the language of the text
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+impl InternationalText {
+    pub const CHUNK_TYPE: &'static str = "iTXt";
+
+    /// Keyword identifying an XMP packet
+    pub const XMP_KEYWORD: &'static str = "XML:com.adobe.xmp";
+
+    /// The only compression method defined by the spec, zlib deflate
+    const COMPRESSION_METHOD: u8 = 0;
+
+    /// Uncompressed text chunk holding an XMP packet. XMP must not be compressed so that tools
+    /// scanning for the packet can find it
+    pub fn xmp(packet: &str) -> Self {
+        Self {
+            keyword: String::from(InternationalText::XMP_KEYWORD),
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: String::from(packet),
+        }
+    }
+
+    /// Whether the chunk is an iTXt chunk with the given keyword. Only the keyword is read, so
+    /// a chunk can be identified even if its text is invalid or too large to inflate
+    pub fn has_keyword(chunk: &Chunk, keyword: &str) -> bool {
+        chunk.chunk_type().matches(InternationalText::CHUNK_TYPE)
+            && read_keyword(chunk.data()).is_some_and(|(k, _)| k == keyword)
+    }
+
+    /// Encode the text as a chunk
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.keyword).ok_or(ChunkDataError::InvalidValue(
+            InternationalText::CHUNK_TYPE,
+            "keyword",
+        ))?;
+
+        if self.language_tag.contains('\0') || self.translated_keyword.contains('\0') {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                InternationalText::CHUNK_TYPE,
+                "language tag or translated keyword",
+            )));
+        }
+
+        data.push(self.compressed as u8);
+        data.push(InternationalText::COMPRESSION_METHOD);
+        data.extend_from_slice(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.translated_keyword.as_bytes());
+        data.push(0);
+
+        if self.compressed {
+            let mut encoder = ZlibEncoder::new(data, Compression::default());
+            encoder.write_all(self.text.as_bytes())?;
+            data = encoder.finish()?;
+        } else {
+            data.extend_from_slice(self.text.as_bytes());
+        }
+
+        Ok(Chunk::new(
+            ChunkType::from_str(InternationalText::CHUNK_TYPE)?,
+            data,
+        ))
+    }
+}
+
+/// Split a NUL-terminated field off the front of the data
+fn split_field(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let end = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(ChunkDataError::InvalidLength(
+            InternationalText::CHUNK_TYPE,
+            data.len(),
+        ))?;
+
+    Ok((&data[..end], &data[end + 1..]))
+}
+
+impl InternationalText {
+    /// Decode the text, enforcing the decompression limits in the given options
+    pub fn parse_with_options(chunk: &Chunk, options: &ParseOptions) -> Result<Self> {
+        expect_chunk_type(chunk, InternationalText::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        let (keyword, keyword_length) = read_keyword(data).ok_or(ChunkDataError::InvalidValue(
+            InternationalText::CHUNK_TYPE,
+            "keyword",
+        ))?;
+        let data = &data[keyword_length..];
+
+        if data.len() < 2 {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                InternationalText::CHUNK_TYPE,
+                chunk.length(),
+            )));
+        }
+
+        let compressed = match (data[0], data[1]) {
+            (0, _) => false,
+            (1, InternationalText::COMPRESSION_METHOD) => true,
+            _ => {
+                return Err(Box::from(ChunkDataError::InvalidValue(
+                    InternationalText::CHUNK_TYPE,
+                    "compression",
+                )))
+            }
+        };
+
+        let (language_tag, data) = split_field(&data[2..])?;
+        let (translated_keyword, data) = split_field(data)?;
+
+        let text = if compressed {
+            String::from_utf8(inflate(InternationalText::CHUNK_TYPE, data, options)?)?
+        } else {
+            String::from(std::str::from_utf8(data)?)
+        };
+
+        Ok(Self {
+            keyword,
+            compressed,
+            language_tag: String::from(std::str::from_utf8(language_tag)?),
+            translated_keyword: String::from(std::str::from_utf8(translated_keyword)?),
+            text,
+        })
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalText {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        InternationalText::parse_with_options(chunk, &ParseOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_text(compressed: bool) -> InternationalText {
+        InternationalText {
+            keyword: String::from("Title"),
+            compressed,
+            language_tag: String::from("de"),
+            translated_keyword: String::from("Titel"),
+            text: String::from("Größenverhältnisse"),
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_text() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("iTXt").unwrap(),
+            b"Title\0\0\0de\0Titel\0Gr\xC3\xB6\xC3\x9Fenverh\xC3\xA4ltnisse".to_vec(),
+        );
+
+        let text = InternationalText::try_from(&chunk).unwrap();
+
+        assert_eq!(text, testing_text(false));
+        assert_eq!(text.to_chunk().unwrap(), chunk);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let chunk = testing_text(true).to_chunk().unwrap();
+        assert_eq!(
+            InternationalText::try_from(&chunk).unwrap(),
+            testing_text(true)
+        );
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let mut text = testing_text(true);
+        text.text = "a".repeat(100_000);
+        let chunk = text.to_chunk().unwrap();
+
+        assert!(InternationalText::try_from(&chunk).is_err());
+
+        let options = ParseOptions {
+            max_inflated_length: usize::MAX,
+            max_inflate_ratio: usize::MAX,
+            ..ParseOptions::default()
+        };
+        let parsed = InternationalText::parse_with_options(&chunk, &options).unwrap();
+        assert_eq!(parsed, text);
+    }
+
+    #[test]
+    fn test_xmp_is_uncompressed() {
+        let packet = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>";
+        let chunk = InternationalText::xmp(packet).to_chunk().unwrap();
+
+        assert!(chunk.data().starts_with(b"XML:com.adobe.xmp\0\0\0"));
+        assert!(chunk.data().ends_with(packet.as_bytes()));
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("iTXt").unwrap(),
+            b"Title\0\0\0de".to_vec(),
+        );
+        assert!(InternationalText::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_invalid_compression_flag() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("iTXt").unwrap(),
+            b"Title\0\x02\0\0\0text".to_vec(),
+        );
+        assert!(InternationalText::try_from(&chunk).is_err());
+    }
+}
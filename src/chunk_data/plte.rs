@@ -0,0 +1,63 @@
+use super::{expect_chunk_type, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::{Error, Result};
+use std::convert::TryFrom;
+
+/// Palette (PLTE) chunk
+///
+/// See [PNG Chunks - PLTE](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.PLTE) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Palette {
+    /// Red, green and blue values of each palette entry
+    pub entries: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub const CHUNK_TYPE: &'static str = "PLTE";
+    const MAX_ENTRIES: usize = 256;
+}
+
+impl TryFrom<&Chunk> for Palette {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, Palette::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        if data.is_empty() || !data.len().is_multiple_of(3) || data.len() / 3 > Palette::MAX_ENTRIES
+        {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                Palette::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        let entries = data.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect();
+
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("PLTE").unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::try_from(&chunk(&[255, 0, 0, 0, 0, 255])).unwrap();
+        assert_eq!(palette.entries, vec![[255, 0, 0], [0, 0, 255]]);
+    }
+
+    #[test]
+    fn test_invalid_palette_length() {
+        assert!(Palette::try_from(&chunk(&[])).is_err());
+        assert!(Palette::try_from(&chunk(&[255, 0])).is_err());
+        assert!(Palette::try_from(&chunk(&[0; 257 * 3])).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use super::{expect_chunk_type, keyword_bytes, read_keyword, read_u16, ChunkDataError};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+use std::{convert::TryFrom, str::FromStr};
+
+/// Single entry of a suggested palette. Samples are stored at the palette's sample depth, so must
+/// fit in 8 bits for 8-bit palettes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+
+    /// Relative frequency of the colour in the image
+    pub frequency: u16,
+}
+
+/// Suggested palette (sPLT) chunk
+///
+/// See [PNG Chunks - sPLT](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.sPLT) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SuggestedPalette {
+    /// Palette name, 1-79 Latin-1 characters
+    pub name: String,
+
+    /// Either 8 or 16
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+impl SuggestedPalette {
+    pub const CHUNK_TYPE: &'static str = "sPLT";
+
+    fn entry_length(sample_depth: u8) -> Option<usize> {
+        match sample_depth {
+            8 => Some(6),
+            16 => Some(10),
+            _ => None,
+        }
+    }
+
+    /// Encode the palette as a chunk
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.name).ok_or(ChunkDataError::InvalidValue(
+            SuggestedPalette::CHUNK_TYPE,
+            "palette name",
+        ))?;
+
+        if SuggestedPalette::entry_length(self.sample_depth).is_none() {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                SuggestedPalette::CHUNK_TYPE,
+                "sample depth",
+            )));
+        }
+
+        data.push(self.sample_depth);
+
+        for entry in &self.entries {
+            for &sample in &[entry.red, entry.green, entry.blue, entry.alpha] {
+                if self.sample_depth == 8 {
+                    data.push(u8::try_from(sample).map_err(|_| {
+                        ChunkDataError::InvalidValue(SuggestedPalette::CHUNK_TYPE, "sample")
+                    })?);
+                } else {
+                    data.extend_from_slice(&sample.to_be_bytes());
+                }
+            }
+
+            data.extend_from_slice(&entry.frequency.to_be_bytes());
+        }
+
+        Ok(Chunk::new(
+            ChunkType::from_str(SuggestedPalette::CHUNK_TYPE)?,
+            data,
+        ))
+    }
+}
+
+impl TryFrom<&Chunk> for SuggestedPalette {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        expect_chunk_type(chunk, SuggestedPalette::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        let (name, name_length) = read_keyword(data).ok_or(ChunkDataError::InvalidValue(
+            SuggestedPalette::CHUNK_TYPE,
+            "palette name",
+        ))?;
+
+        let sample_depth = *data.get(name_length).ok_or(ChunkDataError::InvalidLength(
+            SuggestedPalette::CHUNK_TYPE,
+            data.len(),
+        ))?;
+
+        let entry_length = SuggestedPalette::entry_length(sample_depth).ok_or(
+            ChunkDataError::InvalidValue(SuggestedPalette::CHUNK_TYPE, "sample depth"),
+        )?;
+
+        let entries_data = &data[name_length + 1..];
+
+        if !entries_data.len().is_multiple_of(entry_length) {
+            return Err(Box::from(ChunkDataError::InvalidLength(
+                SuggestedPalette::CHUNK_TYPE,
+                data.len(),
+            )));
+        }
+
+        let entries = entries_data
+            .chunks(entry_length)
+            .map(|entry| {
+                let sample = |i: usize| {
+                    if sample_depth == 8 {
+                        u16::from(entry[i])
+                    } else {
+                        read_u16(entry, i * 2)
+                    }
+                };
+
+                SuggestedPaletteEntry {
+                    red: sample(0),
+                    green: sample(1),
+                    blue: sample(2),
+                    alpha: sample(3),
+                    frequency: read_u16(entry, entry_length - 2),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            name,
+            sample_depth,
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("sPLT").unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_8_bit_palette() {
+        #[rustfmt::skip]
+        let palette = SuggestedPalette::try_from(&chunk(&[
+            b'w', b'e', b'b', 0, 8,
+            255, 0, 0, 255, 0, 10,
+            0, 0, 255, 128, 0, 5,
+        ]))
+        .unwrap();
+
+        assert_eq!(palette.name, "web");
+        assert_eq!(palette.sample_depth, 8);
+        assert_eq!(palette.entries.len(), 2);
+        assert_eq!(palette.entries[0].red, 255);
+        assert_eq!(palette.entries[1].alpha, 128);
+        assert_eq!(palette.entries[1].frequency, 5);
+    }
+
+    #[test]
+    fn test_16_bit_palette() {
+        #[rustfmt::skip]
+        let palette = SuggestedPalette::try_from(&chunk(&[
+            b'h', b'i', 0, 16,
+            1, 0, 0, 2, 0, 3, 255, 255, 0, 7,
+        ]))
+        .unwrap();
+
+        assert_eq!(palette.entries[0].red, 256);
+        assert_eq!(palette.entries[0].green, 2);
+        assert_eq!(palette.entries[0].blue, 3);
+        assert_eq!(palette.entries[0].alpha, 65535);
+        assert_eq!(palette.entries[0].frequency, 7);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [b'p', 0xE9, 0, 16, 1, 0, 0, 2, 0, 3, 255, 255, 0, 7];
+        let palette = SuggestedPalette::try_from(&chunk(&data)).unwrap();
+
+        assert_eq!(palette.name, "p\u{e9}");
+        assert_eq!(palette.to_chunk().unwrap(), chunk(&data));
+    }
+
+    #[test]
+    fn test_8_bit_sample_out_of_range() {
+        let palette = SuggestedPalette {
+            name: String::from("web"),
+            sample_depth: 8,
+            entries: vec![SuggestedPaletteEntry {
+                red: 256,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+                frequency: 1,
+            }],
+        };
+
+        assert!(palette.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_invalid_sample_depth() {
+        assert!(SuggestedPalette::try_from(&chunk(&[b'a', 0, 4])).is_err());
+    }
+
+    #[test]
+    fn test_invalid_entries_length() {
+        assert!(SuggestedPalette::try_from(&chunk(&[b'a', 0, 8, 1, 2, 3])).is_err());
+    }
+
+    #[test]
+    fn test_missing_name() {
+        assert!(SuggestedPalette::try_from(&chunk(&[0, 8])).is_err());
+        assert!(SuggestedPalette::try_from(&chunk(b"ab")).is_err());
+    }
+}
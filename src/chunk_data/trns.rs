@@ -0,0 +1,170 @@
+use super::{
+    expect_chunk_type, fits_bit_depth, read_u16, ChunkDataError, ColorType, ImageHeader, Palette,
+};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::Result;
+use std::str::FromStr;
+
+/// Transparency (tRNS) chunk, whose format depends on the image's colour type. Images with an
+/// alpha channel can't have one
+///
+/// See [PNG Chunks - tRNS](http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.tRNS) for details
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Transparency {
+    /// Grey level treated as fully transparent in greyscale images
+    Gray(u16),
+
+    /// Colour treated as fully transparent in truecolour images
+    Rgb(u16, u16, u16),
+
+    /// Alpha values for the leading palette entries of indexed colour images
+    PaletteAlpha(Vec<u8>),
+}
+
+impl Transparency {
+    pub const CHUNK_TYPE: &'static str = "tRNS";
+
+    /// Interpret a tRNS chunk for an image of the given colour type
+    pub fn parse(chunk: &Chunk, color_type: ColorType) -> Result<Self> {
+        expect_chunk_type(chunk, Transparency::CHUNK_TYPE)?;
+        let data = chunk.data();
+
+        let transparency = match (color_type, data.len()) {
+            (ColorType::Grayscale, 2) => Transparency::Gray(read_u16(data, 0)),
+            (ColorType::Rgb, 6) => {
+                Transparency::Rgb(read_u16(data, 0), read_u16(data, 2), read_u16(data, 4))
+            }
+            (ColorType::Indexed, _) => Transparency::PaletteAlpha(data.to_vec()),
+            (ColorType::GrayscaleAlpha, _) | (ColorType::Rgba, _) => {
+                return Err(Box::from(ChunkDataError::InvalidValue(
+                    Transparency::CHUNK_TYPE,
+                    "colour type",
+                )))
+            }
+            (_, length) => {
+                return Err(Box::from(ChunkDataError::InvalidLength(
+                    Transparency::CHUNK_TYPE,
+                    length,
+                )))
+            }
+        };
+
+        Ok(transparency)
+    }
+
+    /// Check the transparency matches the image's colour type and bit depth, and doesn't have
+    /// more alpha values than there are palette entries for indexed colour images
+    pub fn validate(&self, header: &ImageHeader, palette: Option<&Palette>) -> Result<()> {
+        let valid = match (self, header.color_type) {
+            (Transparency::Gray(gray), ColorType::Grayscale) => {
+                fits_bit_depth(*gray, header.bit_depth)
+            }
+            (Transparency::Rgb(r, g, b), ColorType::Rgb) => [r, g, b]
+                .iter()
+                .all(|&&sample| fits_bit_depth(sample, header.bit_depth)),
+            (Transparency::PaletteAlpha(alphas), ColorType::Indexed) => {
+                palette.is_some_and(|p| alphas.len() <= p.entries.len())
+            }
+            _ => false,
+        };
+
+        if !valid {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                Transparency::CHUNK_TYPE,
+                "transparency",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Encode the transparency as a chunk
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(r, g, b) => [r, g, b]
+                .iter()
+                .flat_map(|s| s.to_be_bytes().to_vec())
+                .collect(),
+            Transparency::PaletteAlpha(alphas) => alphas.clone(),
+        };
+
+        let chunk_type = ChunkType::from_str(Transparency::CHUNK_TYPE).expect("tRNS is valid");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("tRNS").unwrap(), data.to_vec())
+    }
+
+    fn header(color_type: ColorType, bit_depth: u8) -> ImageHeader {
+        ImageHeader {
+            width: 1,
+            height: 1,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_per_color_type() {
+        assert_eq!(
+            Transparency::parse(&chunk(&[0, 7]), ColorType::Grayscale).unwrap(),
+            Transparency::Gray(7)
+        );
+        assert_eq!(
+            Transparency::parse(&chunk(&[0, 1, 0, 2, 1, 0]), ColorType::Rgb).unwrap(),
+            Transparency::Rgb(1, 2, 256)
+        );
+        assert_eq!(
+            Transparency::parse(&chunk(&[0, 128, 255]), ColorType::Indexed).unwrap(),
+            Transparency::PaletteAlpha(vec![0, 128, 255])
+        );
+    }
+
+    #[test]
+    fn test_not_allowed_with_alpha_channel() {
+        assert!(Transparency::parse(&chunk(&[0, 7]), ColorType::GrayscaleAlpha).is_err());
+        assert!(Transparency::parse(&chunk(&[0, 1, 0, 2, 1, 0]), ColorType::Rgba).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [0, 1, 0, 2, 1, 0];
+        let transparency = Transparency::parse(&chunk(&data), ColorType::Rgb).unwrap();
+        assert_eq!(transparency.to_chunk(), chunk(&data));
+    }
+
+    #[test]
+    fn test_validate_bit_depth() {
+        let header = header(ColorType::Rgb, 8);
+        assert!(Transparency::Rgb(255, 0, 0).validate(&header, None).is_ok());
+        assert!(Transparency::Rgb(256, 0, 0)
+            .validate(&header, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_palette_length() {
+        let header = header(ColorType::Indexed, 8);
+        let palette = Palette {
+            entries: vec![[0, 0, 0]; 2],
+        };
+
+        assert!(Transparency::PaletteAlpha(vec![0, 0])
+            .validate(&header, Some(&palette))
+            .is_ok());
+        assert!(Transparency::PaletteAlpha(vec![0, 0, 0])
+            .validate(&header, Some(&palette))
+            .is_err());
+    }
+}
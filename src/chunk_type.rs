@@ -4,7 +4,7 @@ use std::{convert::TryFrom, fmt::Display, str::FromStr};
 /// Chunk Type for v1.2 of the PNG spec
 ///
 /// See [PNG Structure - Chunk Naming Conventions](http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html#Chunk-naming-conventions) for details
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -15,32 +15,34 @@ impl ChunkType {
         self.bytes
     }
 
+    /// Whether this is the given chunk type, compared without allocating a string
+    pub fn matches(&self, chunk_type: &str) -> bool {
+        self.bytes[..] == *chunk_type.as_bytes()
+    }
+
     /// Bytes must only be in the lower-case and upper-case ASCII ranges, and the reserved bit must be valid
     pub fn is_valid(&self) -> bool {
-        let valid_chars = self
-            .bytes
-            .iter()
-            .all(|&b| (b >= b'a' && b <= b'z' || (b >= b'A' && b <= b'Z')));
+        let valid_chars = self.bytes.iter().all(|b| b.is_ascii_alphabetic());
         valid_chars && self.is_reserved_bit_valid()
     }
 
     /// A type code is critical if bit 5 (value 32) of the first byte is 0
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         (self.bytes[0] & 0x20) != 0x20
     }
 
     /// A type code is public if bit 5 (value 32) of the second byte is 0
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         (self.bytes[1] & 0x20) != 0x20
     }
 
     /// Bit 5 of the third byte is reserved and must be 0
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         (self.bytes[2] & 0x20) != 0x20
     }
 
     /// A type code is safe to copy if bit 5 (value 32) of the fourth byte is 1
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         (self.bytes[3] & 0x20) == 0x20
     }
 }
@@ -63,16 +65,14 @@ impl FromStr for ChunkType {
             return Err(Box::new(ChunkTypeError::ByteLengthError(bytes.len())));
         }
 
-        let valid_chars = bytes
-            .iter()
-            .all(|&b| (b >= b'a' && b <= b'z' || (b >= b'A' && b <= b'Z')));
+        let valid_chars = bytes.iter().all(|b| b.is_ascii_alphabetic());
 
         if !valid_chars {
             return Err(Box::new(ChunkTypeError::InvalidCharacter));
         }
 
         let sized: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
-        Ok(ChunkType::try_from(sized)?)
+        ChunkType::try_from(sized)
     }
 }
 
@@ -194,6 +194,14 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_matches() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert!(chunk.matches("RuSt"));
+        assert!(!chunk.matches("rust"));
+        assert!(!chunk.matches("RuStX"));
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -1 +1,2081 @@
+use crate::args::{
+    AnnotateArgs, AnonymizeArgs, DecodeArgs, DumpArgs, EncodeArgs, EncodeMode, ExtractArgs,
+    FramesArgs, IccArgs, InjectArgs, MoveArgs, NewArgs, PayloadArgs, PrintArgs, RemoveArgs,
+    SelftestArgs, SizeBudget, StripArgs, TrailingArgs,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use pngme::chunk::{Chunk, ChunkError};
+use pngme::chunk_data::{Animation, IccProfile, SuggestedPalette};
+use pngme::chunk_type::ChunkType;
+use pngme::file_type::FileType;
+use pngme::parse_options::ParseOptions;
+use pngme::png::{Png, PngError};
+use pngme::strip::StripPreset;
+use pngme::Result;
+use std::{
+    convert::TryFrom,
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+/// Append the message to the PNG file in a chunk of the given type, and embed any XMP packet.
+/// Returns a line comparing the file's size before and after
+pub fn encode(args: EncodeArgs, options: &ParseOptions) -> Result<String> {
+    let mut png = read_png(&args.file_path, options)?;
+    let checksum = pixel_check(&png, args.verify_pixels)?;
+
+    if let (Some(chunk_type), Some(message)) = (args.chunk_type, args.message) {
+        encode_message(&mut png, chunk_type, message, args.mode)?;
+    }
+
+    if let Some(path) = &args.xmp_file {
+        let packet = fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+        png.set_xmp(&packet)?;
+    }
+
+    verify_pixels(checksum, &png)?;
+    apply_trailing_policy(&mut png, &args.trailing);
+
+    let input = &args.file_path;
+    let before = fs::metadata(input).map_err(|e| io_error(input, e))?.len() as usize;
+    let output = args.output_file.as_ref().unwrap_or(input);
+    let bytes = file_bytes(output, &png)?;
+
+    if let Some(budget) = args.max_overhead {
+        if !budget.allows(before, bytes.len()) {
+            return Err(Box::from(CommandError::OverBudget(
+                before,
+                bytes.len(),
+                budget,
+            )));
+        }
+    }
+
+    let after = bytes.len();
+    fs::write(output, bytes).map_err(|e| io_error(output, e))?;
+
+    Ok(describe_growth(before, after))
+}
+
+fn describe_growth(before: usize, after: usize) -> String {
+    let growth = if before == 0 {
+        0.0
+    } else {
+        (after as f64 - before as f64) / before as f64 * 100.0
+    };
+
+    format!("{} bytes -> {} bytes ({:+.1}%)", before, after, growth)
+}
+
+fn encode_message(
+    png: &mut Png,
+    chunk_type: ChunkType,
+    message: String,
+    mode: EncodeMode,
+) -> Result<()> {
+    // an invalid type would be written out but then rejected when the file is read back
+    if !chunk_type.is_valid() {
+        return Err(Box::from(ChunkError::InvalidChunkType));
+    }
+
+    // decoders must refuse to render a file with an unknown critical chunk, and a standard one
+    // (e.g. a second IEND) would corrupt the image
+    if chunk_type.is_critical() {
+        return Err(Box::from(CommandError::CriticalChunkType(
+            chunk_type.to_string(),
+        )));
+    }
+
+    let name = chunk_type.to_string();
+    let exists = png.chunk_by_type(&name).is_some();
+    let chunk = Chunk::new(chunk_type, message.into_bytes());
+
+    match mode {
+        EncodeMode::Replace if exists => {
+            png.replace_chunk(&name, chunk)?;
+        }
+        EncodeMode::Error if exists => {
+            return Err(Box::from(CommandError::ChunkExists(name)));
+        }
+        _ => png.append_chunk(chunk),
+    }
+
+    Ok(())
+}
+
+/// First chunk of the given type, whose data is the hidden message
+pub fn decode(args: DecodeArgs, options: &ParseOptions) -> Result<Chunk> {
+    let png = read_png(&args.file_path, options)?;
+    let chunk = png.find_chunk(&args.chunk_type.to_string())?;
+    Ok(chunk.clone())
+}
+
+/// Remove the first chunk of the given type, or every ancillary chunk matching the selectors,
+/// rewriting the file and returning the removed chunks
+pub fn remove(args: RemoveArgs, options: &ParseOptions) -> Result<Vec<Chunk>> {
+    // without IHDR, IDAT or IEND the file is no longer a valid image
+    if let Some(chunk_type) = args.chunk_type.as_ref().filter(|t| t.is_critical()) {
+        return Err(Box::from(CommandError::CriticalChunkType(
+            chunk_type.to_string(),
+        )));
+    }
+
+    let mut png = read_png(&args.file_path, options)?;
+    let checksum = pixel_check(&png, args.verify_pixels)?;
+
+    let removed = match &args.chunk_type {
+        Some(chunk_type) => vec![png.remove_chunk(&chunk_type.to_string())?],
+        None => {
+            let mut removed = Vec::new();
+            png.retain(|chunk| {
+                // with no selectors nothing is picked, rather than every ancillary chunk
+                let any_selector = args.ancillary || args.all_private || args.larger_than.is_some();
+                let selected = any_selector
+                    && !chunk.chunk_type().is_critical()
+                    && (!args.all_private || !chunk.chunk_type().is_public())
+                    && args.larger_than.is_none_or(|size| chunk.length() > size);
+
+                if selected {
+                    removed.push(chunk.clone());
+                }
+
+                !selected
+            });
+            removed
+        }
+    };
+
+    verify_pixels(checksum, &png)?;
+    apply_trailing_policy(&mut png, &args.trailing);
+
+    write_png(&args.file_path, &png)?;
+
+    Ok(removed)
+}
+
+/// Describe each chunk of the file, with the details of suggested palettes and its entropy if
+/// asked. Returns the lines to print
+pub fn print(args: PrintArgs, options: &ParseOptions) -> Result<Vec<String>> {
+    let png = read_png(&args.file_path, options)?;
+    let suspicious: Vec<usize> = png.high_entropy_chunks().map(|(index, _)| index).collect();
+
+    let lines = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut line = format!(
+                "{}: {}, {} bytes",
+                index,
+                chunk.chunk_type(),
+                chunk.length()
+            );
+
+            if chunk.chunk_type().matches(SuggestedPalette::CHUNK_TYPE) {
+                line.push_str(&match SuggestedPalette::try_from(chunk) {
+                    Ok(palette) => format!(
+                        ", palette \"{}\" with {} {}-bit entries",
+                        palette.name,
+                        palette.entries.len(),
+                        palette.sample_depth
+                    ),
+                    Err(e) => format!(", {}", e),
+                });
+            }
+
+            if args.entropy {
+                line.push_str(&format!(", entropy {:.2} bits/byte", chunk.entropy()));
+
+                if suspicious.contains(&index) {
+                    line.push_str(" (looks compressed or encrypted)");
+                }
+            }
+
+            line
+        })
+        .collect();
+
+    Ok(lines)
+}
+
+/// First chunk of the given type as it's stored in the file: length, type, data and CRC
+pub fn dump(args: DumpArgs, options: &ParseOptions) -> Result<Vec<u8>> {
+    let png = read_png(&args.file_path, options)?;
+    let chunk = png.find_chunk(&args.chunk_type.to_string())?;
+    Ok(chunk.as_bytes())
+}
+
+/// Insert a chunk read from a file, checking its CRC and type first, and rewrite the PNG file.
+/// Returns the inserted chunk
+pub fn inject(args: InjectArgs, options: &ParseOptions) -> Result<Chunk> {
+    let bytes = fs::read(&args.chunk).map_err(|e| io_error(&args.chunk, e))?;
+    let chunk = Chunk::parse_with_options(&bytes, options)?;
+
+    // anything left over means the file wasn't a single chunk, so it's probably not what was meant
+    let extra = bytes.len() - (chunk.length() + Chunk::METADATA_BYTES);
+    if extra > 0 {
+        return Err(Box::from(CommandError::ExtraChunkBytes(extra)));
+    }
+
+    // same reasoning as encode: a critical chunk would stop the image rendering or corrupt it
+    if chunk.chunk_type().is_critical() {
+        return Err(Box::from(CommandError::CriticalChunkType(
+            chunk.chunk_type().to_string(),
+        )));
+    }
+
+    let mut png = read_png(&args.file_path, options)?;
+
+    match args.position {
+        Some(index) => png.insert_chunk(index, chunk.clone())?,
+        None => png.append_chunk(chunk.clone()),
+    }
+
+    apply_trailing_policy(&mut png, &args.trailing);
+    write_png(&args.file_path, &png)?;
+
+    Ok(chunk)
+}
+
+/// Move the first chunk of the given type so that it ends up at the given index, and rewrite the
+/// file
+pub fn move_chunk(args: MoveArgs, options: &ParseOptions) -> Result<()> {
+    // IHDR and IEND have to stay at either end, and IDAT chunks have to stay together
+    if args.chunk_type.is_critical() {
+        return Err(Box::from(CommandError::CriticalChunkType(
+            args.chunk_type.to_string(),
+        )));
+    }
+
+    let mut png = read_png(&args.file_path, options)?;
+    let chunk = png.remove_chunk(&args.chunk_type.to_string())?;
+    png.insert_chunk(args.position, chunk)?;
+
+    apply_trailing_policy(&mut png, &args.trailing);
+    write_png(&args.file_path, &png)?;
+
+    Ok(())
+}
+
+/// Write the data appended after IEND to a file and describe it, and print the XMP packet.
+/// Returns the lines to print
+pub fn extract(args: ExtractArgs, options: &ParseOptions) -> Result<Vec<String>> {
+    let png = read_png(&args.file_path, options)?;
+    let mut lines = Vec::new();
+
+    if let Some(path) = &args.trailing {
+        let trailing = png.trailing_data();
+        if trailing.is_empty() {
+            return Err(Box::from(CommandError::NoTrailingData));
+        }
+
+        fs::write(path, trailing).map_err(|e| io_error(path, e))?;
+
+        lines.push(match png.trailing_data_type() {
+            Some(file_type) => format!("Extracted {} bytes of {} data", trailing.len(), file_type),
+            None => format!("Extracted {} bytes of unrecognised data", trailing.len()),
+        });
+    }
+
+    if args.xmp {
+        let packet = png.xmp()?.ok_or(CommandError::NoXmp)?;
+        lines.extend(pretty_xml(&packet));
+    }
+
+    Ok(lines)
+}
+
+/// Put each element of an XML document on its own line, indented by its depth. Elements holding
+/// only text stay on one line. This is only meant for reading, so it isn't a full XML parser
+fn pretty_xml(xml: &str) -> Vec<String> {
+    enum Token<'a> {
+        Open(&'a str),
+        Close(&'a str),
+        Empty(&'a str),
+        Text(&'a str),
+    }
+
+    let mut tokens = Vec::new();
+    let mut rest = xml;
+    while !rest.is_empty() {
+        if rest.starts_with('<') {
+            let terminator = if rest.starts_with("<!--") { "-->" } else { ">" };
+            let end = rest
+                .find(terminator)
+                .map_or(rest.len(), |i| i + terminator.len());
+            let tag = &rest[..end];
+            tokens.push(if tag.starts_with("</") {
+                Token::Close(tag)
+            } else if tag.starts_with("<?") || tag.starts_with("<!") || tag.ends_with("/>") {
+                Token::Empty(tag)
+            } else {
+                Token::Open(tag)
+            });
+            rest = &rest[end..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = rest[..end].trim();
+            if !text.is_empty() {
+                tokens.push(Token::Text(text));
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let indent = "  ".repeat(depth);
+        match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            (Token::Open(open), Some(Token::Text(text)), Some(Token::Close(close))) => {
+                lines.push(format!("{}{}{}{}", indent, open, text, close));
+                i += 3;
+                continue;
+            }
+            (Token::Open(tag), _, _) => {
+                lines.push(format!("{}{}", indent, tag));
+                depth += 1;
+            }
+            (Token::Close(tag), _, _) => {
+                depth = depth.saturating_sub(1);
+                lines.push(format!("{}{}", "  ".repeat(depth), tag));
+            }
+            (Token::Empty(text), _, _) | (Token::Text(text), _, _) => {
+                lines.push(format!("{}{}", indent, text));
+            }
+        }
+        i += 1;
+    }
+
+    lines
+}
+
+/// List the frames of an animated PNG, or write each to its own file. Returns the lines to print:
+/// a description of the animation and each frame, or the paths written
+pub fn frames(args: FramesArgs, options: &ParseOptions) -> Result<Vec<String>> {
+    let png = read_png(&args.file_path, options)?;
+    let animation = png.animation()?.ok_or(PngError::NotAnimated)?;
+
+    let directory = match args.extract {
+        Some(directory) => directory,
+        None => return Ok(describe_animation(&animation)),
+    };
+
+    fs::create_dir_all(&directory).map_err(|e| io_error(&directory, e))?;
+
+    let mut written = Vec::with_capacity(animation.frames.len());
+    for index in 0..animation.frames.len() {
+        let path = directory.join(format!("frame-{:03}.png", index));
+        write_png(&path, &png.extract_frame(index)?)?;
+        written.push(path.display().to_string());
+    }
+
+    Ok(written)
+}
+
+fn describe_animation(animation: &Animation) -> Vec<String> {
+    let plays = match animation.control.num_plays {
+        0 => String::from("looping forever"),
+        1 => String::from("playing once"),
+        n => format!("playing {} times", n),
+    };
+
+    let mut lines = vec![format!("{} frames, {}", animation.frames.len(), plays)];
+    lines.extend(animation.frames.iter().enumerate().map(|(index, frame)| {
+        let control = &frame.control;
+        format!(
+            "frame {}: {}x{} at ({}, {}), delay {}s, dispose {:?}, blend {:?}",
+            index,
+            control.width,
+            control.height,
+            control.x_offset,
+            control.y_offset,
+            control.delay(),
+            control.dispose_op,
+            control.blend_op
+        )
+    }));
+
+    lines
+}
+
+/// Extract or embed an ICC colour profile
+pub fn icc(args: IccArgs, options: &ParseOptions) -> Result<()> {
+    match args {
+        IccArgs::Extract {
+            file_path,
+            output_file,
+        } => {
+            let png = read_png(&file_path, options)?;
+            let profile = png.icc_profile()?.ok_or(CommandError::NoIccProfile)?;
+            fs::write(&output_file, profile.profile).map_err(|e| io_error(&output_file, e))
+        }
+        IccArgs::Insert {
+            file_path,
+            profile_file,
+            name,
+            trailing,
+        } => {
+            let mut png = read_png(&file_path, options)?;
+            let profile = fs::read(&profile_file).map_err(|e| io_error(&profile_file, e))?;
+            png.set_icc_profile(&IccProfile { name, profile })?;
+            apply_trailing_policy(&mut png, &trailing);
+            write_png(&file_path, &png)
+        }
+    }
+}
+
+/// Set, get or list provenance annotations, returning the lines to print
+pub fn annotate(args: AnnotateArgs, options: &ParseOptions) -> Result<Vec<String>> {
+    match args {
+        AnnotateArgs::Set {
+            file_path,
+            key,
+            value,
+            trailing,
+        } => {
+            let mut png = read_png(&file_path, options)?;
+            let mut annotations = png.annotations()?;
+            annotations.insert(&key, &value)?;
+            png.set_annotations(&annotations);
+            apply_trailing_policy(&mut png, &trailing);
+            write_png(&file_path, &png)?;
+            Ok(Vec::new())
+        }
+        AnnotateArgs::Get { file_path, key } => {
+            let annotations = read_png(&file_path, options)?.annotations()?;
+            let value = annotations
+                .get(&key)
+                .ok_or_else(|| CommandError::NoAnnotation(key.clone()))?;
+            Ok(vec![value.to_string()])
+        }
+        AnnotateArgs::List { file_path, prefix } => {
+            let annotations = read_png(&file_path, options)?.annotations()?;
+            Ok(annotations
+                .with_prefix(&prefix)
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect())
+        }
+    }
+}
+
+/// Remove the chunks selected by the preset, rewriting the file and returning the removed chunks
+pub fn strip(args: StripArgs, options: &ParseOptions) -> Result<Vec<Chunk>> {
+    let mut png = read_png(&args.file_path, options)?;
+    let removed = png.strip(args.preset);
+    apply_trailing_policy(&mut png, &args.trailing);
+
+    write_png(&args.file_path, &png)?;
+
+    Ok(removed)
+}
+
+/// Strip and normalise the file so it can't be fingerprinted
+pub fn anonymize(args: AnonymizeArgs, options: &ParseOptions) -> Result<()> {
+    let mut png = read_png(&args.file_path, options)?;
+    let checksum = pixel_check(&png, args.verify_pixels)?;
+
+    let trailing_length = png.trailing_data().len();
+    png.anonymize()?;
+    verify_pixels(checksum, &png)?;
+
+    if trailing_length > 0 {
+        eprintln!("Removed {} bytes of data after IEND", trailing_length);
+    }
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    write_png(output, &png)
+}
+
+/// Generate a solid colour or noise carrier image
+pub fn new(args: NewArgs) -> Result<()> {
+    let (width, height) = match &args.fit_payload {
+        Some(path) => fit_payload(fs::metadata(path).map_err(|e| io_error(path, e))?.len()),
+        None => args.size.unwrap_or((1, 1)),
+    };
+
+    let png = match args.noise {
+        Some(seed) => Png::noise(width, height, seed.unwrap_or_else(random_seed))?,
+        None if args.gradient => Png::gradient(width, height)?,
+        None => Png::solid_color(width, height, args.color.unwrap_or([255, 255, 255]))?,
+    };
+
+    write_png(&args.out, &png)
+}
+
+/// Smallest square image whose raw RGB pixels take up at least the given number of bytes
+fn fit_payload(bytes: u64) -> (u32, u32) {
+    let pixels = bytes.div_ceil(3);
+    let mut side = (pixels as f64).sqrt() as u64;
+    while side * side < pixels {
+        side += 1;
+    }
+
+    let side = side.clamp(1, u64::from(Png::MAX_GENERATED_DIMENSION)) as u32;
+    (side, side)
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Apply each kind of edit to a copy of the image, or to a generated one, and check that the
+/// pixels are unchanged and the result can be read back. Returns a line per check, or the checks
+/// which failed
+pub fn selftest(args: SelftestArgs, options: &ParseOptions) -> Result<Vec<String>> {
+    let original = match &args.file_path {
+        Some(path) => read_png(path, options)?,
+        None => Png::noise(64, 64, 0)?,
+    };
+
+    let edits: [(&str, Edit); 6] = [
+        ("append a chunk", |png: &mut Png| {
+            let chunk_type = ChunkType::from_str("ruSt")?;
+            png.append_chunk(Chunk::new(chunk_type, b"pngme selftest".to_vec()));
+            Ok(())
+        }),
+        ("annotate", |png: &mut Png| {
+            let mut annotations = png.annotations()?;
+            annotations.insert("pngme.selftest", "1")?;
+            png.set_annotations(&annotations);
+            Ok(())
+        }),
+        ("embed XMP", |png: &mut Png| {
+            png.set_xmp("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>")
+        }),
+        ("strip", |png: &mut Png| {
+            png.strip(StripPreset::Privacy);
+            Ok(())
+        }),
+        ("re-chunk image data", |png: &mut Png| {
+            png.rechunk_image_data(1024)
+        }),
+        ("anonymize", |png: &mut Png| png.anonymize()),
+    ];
+
+    let mut lines = vec![String::from(SELFTEST_DECODER)];
+    let mut failures = Vec::new();
+
+    for (name, edit) in edits.iter() {
+        let mut png = Png::from_chunks(original.chunks().to_vec());
+        let result = pixel_check(&png, true).and_then(|check| {
+            edit(&mut png)?;
+            verify_pixels(check, &png)?;
+            Png::try_from(&png.as_bytes()[..])?;
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => lines.push(format!("{}: ok", name)),
+            Err(e) => failures.push(format!("{}: {}", name, e)),
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(Box::from(CommandError::SelftestFailed(failures)));
+    }
+
+    Ok(lines)
+}
+
+/// An edit applied to a copy of the image by selftest
+type Edit = fn(&mut Png) -> Result<()>;
+
+#[cfg(feature = "decoder-check")]
+const SELFTEST_DECODER: &str = "Comparing pixels decoded by the png crate";
+
+#[cfg(not(feature = "decoder-check"))]
+const SELFTEST_DECODER: &str = "Comparing decompressed image data. Build with --features \
+                                decoder-check to also compare pixels decoded by the png crate";
+
+/// Run pngme-<command> from PATH with the remaining arguments, returning its exit code
+pub fn external(args: &[OsString]) -> Result<i32> {
+    let (command, rest) = args.split_first().ok_or(CommandError::NoCommand)?;
+    let mut program = OsString::from("pngme-");
+    program.push(command);
+
+    let status = process::Command::new(&program)
+        .args(rest)
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Box::from(CommandError::UnknownCommand(
+                command.to_string_lossy().into_owned(),
+            )),
+            _ => io_error(Path::new(&program), e),
+        })?;
+
+    // killed by a signal, so there's no code to pass on
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Keep or remove any data after IEND before the file is rewritten. Unless the policy was given
+/// explicitly, keeping it is reported on stderr so it isn't carried along unnoticed
+fn apply_trailing_policy(png: &mut Png, args: &TrailingArgs) {
+    if args.strip_trailing {
+        let removed = png.strip_trailing_data();
+        if !removed.is_empty() {
+            eprintln!("Removed {} bytes of data after IEND", removed.len());
+        }
+    } else if !args.keep_trailing && !png.trailing_data().is_empty() {
+        let description = match png.trailing_data_type() {
+            Some(file_type) => format!("{} data", file_type),
+            None => String::from("data"),
+        };
+        eprintln!(
+            "Warning: keeping {} bytes of {} after IEND. Use --strip-trailing to remove it, or \
+             --keep-trailing to silence this warning",
+            png.trailing_data().len(),
+            description
+        );
+    }
+}
+
+/// Read and parse a PNG file, checking its size before loading it into memory and reporting any
+/// problems tolerated by lenient parsing on stderr
+fn read_png(path: &Path, options: &ParseOptions) -> Result<Png> {
+    let size = fs::metadata(path).map_err(|e| io_error(path, e))?.len();
+    if size > options.max_file_size as u64 {
+        return Err(Box::from(PngError::TooLarge(
+            size as usize,
+            options.max_file_size,
+        )));
+    }
+
+    let mut bytes = fs::read(path).map_err(|e| io_error(path, e))?;
+
+    if FileType::sniff(&bytes) == Some(FileType::Gzip) {
+        bytes = gunzip(&bytes, options.max_file_size).map_err(|e| io_error(path, e))?;
+    }
+
+    let png = Png::parse_with_options(&bytes, options)?;
+
+    for warning in png.warnings() {
+        eprintln!("Warning: {}: {}", path.display(), warning);
+    }
+
+    Ok(png)
+}
+
+/// Write a payload to a file or to stdout. On stdout it's written exactly as stored, with no added
+/// newline or lossy UTF-8 conversion, but binary payloads are refused when stdout is a terminal
+/// unless --raw is given, since they'd garble it
+pub fn write_payload(data: &[u8], args: &PayloadArgs) -> Result<()> {
+    let stdout = io::stdout();
+    let terminal = stdout.is_terminal();
+    write_payload_to(data, args, terminal, &mut stdout.lock())
+}
+
+fn write_payload_to<W: Write>(
+    data: &[u8],
+    args: &PayloadArgs,
+    terminal: bool,
+    stdout: &mut W,
+) -> Result<()> {
+    if let Some(path) = &args.out {
+        return fs::write(path, data).map_err(|e| io_error(path, e));
+    }
+
+    if terminal && !args.raw && is_binary(data) {
+        return Err(Box::from(CommandError::BinaryPayload(data.len())));
+    }
+
+    stdout.write_all(data)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Whether the data would garble a terminal: not UTF-8, or containing control characters other
+/// than whitespace
+fn is_binary(data: &[u8]) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(text) => text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')),
+        Err(_) => true,
+    }
+}
+
+/// State of the image before an edit, so it can be checked afterwards
+struct PixelCheck {
+    checksum: u32,
+
+    #[cfg(feature = "decoder-check")]
+    decoded: (::png::OutputInfo, Vec<u8>),
+}
+
+/// Checksum of the image data before an edit, if it's to be verified afterwards
+fn pixel_check(png: &Png, verify: bool) -> Result<Option<PixelCheck>> {
+    if !verify {
+        return Ok(None);
+    }
+
+    Ok(Some(PixelCheck {
+        checksum: png.image_data_checksum()?,
+
+        #[cfg(feature = "decoder-check")]
+        decoded: decode_with_png_crate(png)?,
+    }))
+}
+
+/// Fail if the image data no longer matches that taken before the edit
+fn verify_pixels(before: Option<PixelCheck>, png: &Png) -> Result<()> {
+    if let Some(before) = before {
+        let after = png.image_data_checksum()?;
+        if after != before.checksum {
+            return Err(Box::from(CommandError::PixelsChanged(
+                before.checksum,
+                after,
+            )));
+        }
+
+        #[cfg(feature = "decoder-check")]
+        if decode_with_png_crate(png)? != before.decoded {
+            return Err(Box::from(CommandError::DecoderMismatch));
+        }
+    }
+
+    Ok(())
+}
+
+/// Pixels as decoded by the independent png crate, which also checks everything our own parser
+/// doesn't, such as chunk ordering
+#[cfg(feature = "decoder-check")]
+fn decode_with_png_crate(png: &Png) -> Result<(::png::OutputInfo, Vec<u8>)> {
+    let bytes = png.as_bytes();
+    let mut reader = ::png::Decoder::new(&bytes[..])
+        .read_info()
+        .map_err(|e| CommandError::Undecodable(e.to_string()))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut pixels)
+        .map_err(|e| CommandError::Undecodable(e.to_string()))?;
+    pixels.truncate(info.buffer_size());
+    Ok((info, pixels))
+}
+
+/// Decompress a gzipped file, failing once it's larger than the given size, since a small gzip
+/// file can expand enormously
+fn gunzip(bytes: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(bytes)
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+
+    if decompressed.len() > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompresses to more than {} bytes", max_size),
+        ));
+    }
+
+    Ok(decompressed)
+}
+
+fn write_png(path: &Path, png: &Png) -> Result<()> {
+    let bytes = file_bytes(path, png)?;
+    fs::write(path, bytes).map_err(|e| io_error(path, e))
+}
+
+/// Bytes to write the file to the given path as, gzipped if the path ends in .gz
+fn file_bytes(path: &Path, png: &Png) -> Result<Vec<u8>> {
+    let bytes = png.as_bytes();
+
+    if path.extension().is_some_and(|e| e == "gz") {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes)?;
+        return Ok(encoder.finish()?);
+    }
+
+    Ok(bytes)
+}
+
+fn io_error(path: &Path, error: io::Error) -> pngme::Error {
+    Box::from(CommandError::Io(path.to_path_buf(), error))
+}
+
+/// Errors from running a command which aren't about the file's contents
+#[derive(Debug)]
+pub enum CommandError {
+    /// Critical chunks can't be written or removed, as that would break the image
+    CriticalChunkType(String),
+
+    /// Nothing follows the IEND chunk
+    NoTrailingData,
+
+    /// The file has no iCCP chunk
+    NoIccProfile,
+
+    /// The file has no XMP packet
+    NoXmp,
+
+    /// A chunk file has this many bytes after the end of the chunk
+    ExtraChunkBytes(usize),
+
+    /// The file has no annotation with the given key
+    NoAnnotation(String),
+
+    /// Encoding with --mode error, and the file already has a chunk of this type
+    ChunkExists(String),
+
+    /// --verify-pixels found the image data checksum changed from the first value to the second
+    PixelsChanged(u32, u32),
+
+    /// No command was given
+    NoCommand,
+
+    /// The command isn't built in and there's no pngme-<command> on PATH
+    UnknownCommand(String),
+
+    /// The selftest checks which failed, with their reasons
+    SelftestFailed(Vec<String>),
+
+    /// The file would grow from the first size to the second, more than the budget allows
+    OverBudget(usize, usize, SizeBudget),
+
+    /// The png crate decodes different pixels after an edit
+    #[cfg(feature = "decoder-check")]
+    DecoderMismatch,
+
+    /// The png crate can't decode the image, with its reason
+    #[cfg(feature = "decoder-check")]
+    Undecodable(String),
+
+    /// A binary payload of the given length wasn't written to a terminal
+    BinaryPayload(usize),
+
+    /// Reading or writing the given file failed
+    Io(PathBuf, io::Error),
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CommandError::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::CriticalChunkType(chunk_type) => write!(
+                f,
+                "'{}' is a critical chunk type. Use an ancillary type, starting with a lower-case \
+                 letter, so decoders can ignore it",
+                chunk_type
+            ),
+            CommandError::NoTrailingData => {
+                write!(f, "The file has no data after its IEND chunk")
+            }
+            CommandError::NoIccProfile => write!(f, "The file has no embedded ICC profile"),
+            CommandError::NoXmp => write!(f, "The file has no XMP metadata"),
+            CommandError::ExtraChunkBytes(extra) => write!(
+                f,
+                "The chunk file has {} bytes after the chunk's CRC, but should hold a single chunk",
+                extra
+            ),
+            CommandError::NoAnnotation(key) => write!(f, "The file has no '{}' annotation", key),
+            CommandError::ChunkExists(chunk_type) => write!(
+                f,
+                "The file already has a '{}' chunk. Use --mode append or --mode replace to \
+                 encode anyway",
+                chunk_type
+            ),
+            CommandError::PixelsChanged(before, after) => write!(
+                f,
+                "The image data changed (checksum {:08x} became {:08x}), so the file wasn't written",
+                before, after
+            ),
+            CommandError::NoCommand => write!(f, "No command was given"),
+            CommandError::UnknownCommand(command) => write!(
+                f,
+                "'{}' isn't a pngme command, and there's no pngme-{} on PATH",
+                command, command
+            ),
+            CommandError::SelftestFailed(failures) => {
+                write!(f, "{} selftest checks failed", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {}", failure)?;
+                }
+                Ok(())
+            }
+            CommandError::OverBudget(before, after, budget) => write!(
+                f,
+                "The file would grow from {} to {} bytes, more than the budget of {}, so it wasn't written",
+                before, after, budget
+            ),
+            #[cfg(feature = "decoder-check")]
+            CommandError::DecoderMismatch => write!(
+                f,
+                "The png crate decodes different pixels after the edit, so the file wasn't written"
+            ),
+            #[cfg(feature = "decoder-check")]
+            CommandError::Undecodable(reason) => {
+                write!(f, "The png crate can't decode the image: {}", reason)
+            }
+            CommandError::BinaryPayload(length) => write!(
+                f,
+                "The payload is {} bytes of binary data and stdout is a terminal. Use --out <file> \
+                 to save it, or --raw to print it anyway",
+                length
+            ),
+            CommandError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk_data::SuggestedPaletteEntry;
+    use pngme::png::ParseWarning;
+
+    /// Small carrier image in a temporary file unique to the calling test, deleted on drop
+    struct TestFile(PathBuf);
+
+    impl TestFile {
+        fn new(name: &str) -> Self {
+            TestFile::with_png(name, &Png::solid_color(4, 4, [255, 0, 0]).unwrap())
+        }
+
+        fn with_png(name: &str, png: &Png) -> Self {
+            let file_name = format!("pngme-{}-{}.png", name, std::process::id());
+            let path = std::env::temp_dir().join(file_name);
+            fs::write(&path, png.as_bytes()).unwrap();
+            TestFile(path)
+        }
+    }
+
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn options() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    fn encode_args(file_path: &Path, chunk_type: &str, message: &str) -> EncodeArgs {
+        EncodeArgs {
+            file_path: file_path.to_path_buf(),
+            chunk_type: Some(ChunkType::from_str(chunk_type).unwrap()),
+            message: Some(String::from(message)),
+            output_file: None,
+            xmp_file: None,
+            mode: EncodeMode::Append,
+            trailing: TrailingArgs::default(),
+            verify_pixels: true,
+            max_overhead: None,
+        }
+    }
+
+    fn decode_args(file_path: &Path, chunk_type: &str) -> DecodeArgs {
+        DecodeArgs {
+            file_path: file_path.to_path_buf(),
+            chunk_type: ChunkType::from_str(chunk_type).unwrap(),
+            payload: payload_args(None, false),
+        }
+    }
+
+    fn payload_args(out: Option<&Path>, raw: bool) -> PayloadArgs {
+        PayloadArgs {
+            out: out.map(Path::to_path_buf),
+            raw,
+        }
+    }
+
+    #[test]
+    fn test_encode_then_decode() {
+        let file = TestFile::new("encode-decode");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let chunk = decode(decode_args(&file.0, "ruSt"), &options()).unwrap();
+
+        assert_eq!(chunk.data(), b"hidden message");
+    }
+
+    #[test]
+    fn test_encode_inserts_before_end() {
+        let file = TestFile::new("encode-before-end");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let png = read_png(&file.0, &options()).unwrap();
+        let last_two: Vec<String> = png.chunks()[png.chunks().len() - 2..]
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(last_two, vec!["ruSt", "IEND"]);
+        assert!(png.trailing_data().is_empty());
+    }
+
+    #[test]
+    fn test_encode_to_output_file() {
+        let file = TestFile::new("encode-input");
+        let output = TestFile::new("encode-output");
+        let mut args = encode_args(&file.0, "ruSt", "hidden message");
+        args.output_file = Some(output.0.clone());
+
+        encode(args, &options()).unwrap();
+
+        assert!(decode(decode_args(&file.0, "ruSt"), &options()).is_err());
+        assert!(decode(decode_args(&output.0, "ruSt"), &options()).is_ok());
+    }
+
+    fn encode_twice(name: &str, mode: EncodeMode) -> (TestFile, Result<String>) {
+        let file = TestFile::new(name);
+        encode(encode_args(&file.0, "ruSt", "first"), &options()).unwrap();
+
+        let mut args = encode_args(&file.0, "ruSt", "second");
+        args.mode = mode;
+        let result = encode(args, &options());
+
+        (file, result)
+    }
+
+    fn messages(path: &Path) -> Vec<Vec<u8>> {
+        read_png(path, &options())
+            .unwrap()
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().matches("ruSt"))
+            .map(|c| c.data().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_mode_append() {
+        let (file, result) = encode_twice("mode-append", EncodeMode::Append);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            messages(&file.0),
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_encode_mode_replace() {
+        let (file, result) = encode_twice("mode-replace", EncodeMode::Replace);
+
+        assert!(result.is_ok());
+        assert_eq!(messages(&file.0), vec![b"second".to_vec()]);
+    }
+
+    #[test]
+    fn test_encode_mode_error() {
+        let (file, result) = encode_twice("mode-error", EncodeMode::Error);
+
+        assert!(result.is_err());
+        assert_eq!(messages(&file.0), vec![b"first".to_vec()]);
+    }
+
+    fn append_trailing_data(path: &Path) {
+        let mut bytes = fs::read(path).unwrap();
+        bytes.extend_from_slice(b"PK\x03\x04secret");
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_encode_keeps_trailing_data_by_default() {
+        let file = TestFile::new("keep-trailing");
+        append_trailing_data(&file.0);
+
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let png = read_png(&file.0, &options()).unwrap();
+        assert_eq!(png.trailing_data(), b"PK\x03\x04secret");
+    }
+
+    #[test]
+    fn test_encode_strip_trailing() {
+        let file = TestFile::new("strip-trailing");
+        append_trailing_data(&file.0);
+        let mut args = encode_args(&file.0, "ruSt", "hidden message");
+        args.trailing.strip_trailing = true;
+
+        encode(args, &options()).unwrap();
+
+        let png = read_png(&file.0, &options()).unwrap();
+        assert!(png.trailing_data().is_empty());
+        assert!(png.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_encode_reports_growth() {
+        let file = TestFile::new("growth");
+        let before = fs::metadata(&file.0).unwrap().len() as usize;
+
+        let report = encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        assert_eq!(report, describe_growth(before, before + 26));
+        assert_eq!(describe_growth(200, 250), "200 bytes -> 250 bytes (+25.0%)");
+        assert_eq!(describe_growth(200, 199), "200 bytes -> 199 bytes (-0.5%)");
+    }
+
+    #[test]
+    fn test_encode_max_overhead() {
+        let file = TestFile::new("max-overhead");
+        let original = fs::read(&file.0).unwrap();
+
+        // 12 bytes of length, type and CRC plus the message
+        let mut args = encode_args(&file.0, "ruSt", "hidden message");
+        args.max_overhead = Some(SizeBudget::Bytes(25));
+        assert_eq!(
+            encode(args, &options()).unwrap_err().to_string(),
+            format!(
+                "The file would grow from {} to {} bytes, more than the budget of 25 bytes, so it wasn't written",
+                original.len(),
+                original.len() + 26
+            )
+        );
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+
+        let mut args = encode_args(&file.0, "ruSt", "hidden message");
+        args.max_overhead = Some(SizeBudget::Bytes(26));
+        assert!(encode(args, &options()).is_ok());
+    }
+
+    #[test]
+    fn test_encode_rejects_critical_chunk_types() {
+        let file = TestFile::new("encode-critical");
+        let original = fs::read(&file.0).unwrap();
+
+        assert!(encode(encode_args(&file.0, "IEND", "x"), &options()).is_err());
+        assert!(encode(encode_args(&file.0, "RuST", "x"), &options()).is_err());
+        assert!(encode(encode_args(&file.0, "rust", "x"), &options()).is_err());
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+
+    fn remove_args(file_path: &Path, chunk_type: &str) -> RemoveArgs {
+        RemoveArgs {
+            file_path: file_path.to_path_buf(),
+            chunk_type: Some(ChunkType::from_str(chunk_type).unwrap()),
+            all_private: false,
+            ancillary: false,
+            larger_than: None,
+            payload: payload_args(None, false),
+            trailing: TrailingArgs::default(),
+            verify_pixels: true,
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let file = TestFile::new("remove");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let removed = remove(remove_args(&file.0, "ruSt"), &options()).unwrap();
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].data(), b"hidden message");
+        assert!(decode(decode_args(&file.0, "ruSt"), &options()).is_err());
+    }
+
+    #[test]
+    fn test_remove_selected() {
+        let mut png = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        for (chunk_type, length) in &[("ruSt", 4), ("ruSt", 64), ("tEXt", 64), ("teSt", 2)] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str(chunk_type).unwrap(),
+                vec![b'a'; *length],
+            ));
+        }
+        let file = TestFile::with_png("remove-selected", &png);
+        let chunk_types = |png: &Png| -> Vec<String> {
+            png.chunks()
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect()
+        };
+
+        let mut args = remove_args(&file.0, "ruSt");
+        args.chunk_type = None;
+        args.all_private = true;
+        args.larger_than = Some(16);
+        let removed = remove(args, &options()).unwrap();
+
+        assert_eq!(chunk_types(&Png::from_chunks(removed)), vec!["ruSt"]);
+        assert_eq!(
+            chunk_types(&read_png(&file.0, &options()).unwrap()),
+            vec!["IHDR", "IDAT", "ruSt", "tEXt", "teSt", "IEND"]
+        );
+
+        let mut args = remove_args(&file.0, "ruSt");
+        args.chunk_type = None;
+        args.ancillary = true;
+        let removed = remove(args, &options()).unwrap();
+
+        assert_eq!(removed.len(), 3);
+        assert_eq!(
+            chunk_types(&read_png(&file.0, &options()).unwrap()),
+            vec!["IHDR", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_remove_rejects_critical_chunk_types() {
+        let file = TestFile::new("remove-critical");
+        let original = fs::read(&file.0).unwrap();
+
+        for chunk_type in &["IHDR", "IDAT", "IEND"] {
+            assert!(remove(remove_args(&file.0, chunk_type), &options()).is_err());
+        }
+
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+
+    #[test]
+    fn test_payload_written_unmodified_when_piped() {
+        let mut stdout = Vec::new();
+        let binary = [0, 159, 146, 150, 10];
+
+        write_payload_to(&binary, &payload_args(None, false), false, &mut stdout).unwrap();
+
+        assert_eq!(stdout, binary);
+    }
+
+    #[test]
+    fn test_binary_payload_refused_on_terminal() {
+        let mut stdout = Vec::new();
+
+        let error = write_payload_to(b"\x1b[2J", &payload_args(None, false), true, &mut stdout)
+            .unwrap_err();
+
+        assert!(stdout.is_empty());
+        assert_eq!(
+            error.to_string(),
+            "The payload is 4 bytes of binary data and stdout is a terminal. Use --out <file> to \
+             save it, or --raw to print it anyway"
+        );
+    }
+
+    #[test]
+    fn test_text_payload_written_to_terminal() {
+        let mut stdout = Vec::new();
+
+        write_payload_to(
+            b"hidden\tmessage\n",
+            &payload_args(None, false),
+            true,
+            &mut stdout,
+        )
+        .unwrap();
+
+        assert_eq!(stdout, b"hidden\tmessage\n");
+    }
+
+    #[test]
+    fn test_raw_payload_written_to_terminal() {
+        let mut stdout = Vec::new();
+
+        write_payload_to(&[0, 255], &payload_args(None, true), true, &mut stdout).unwrap();
+
+        assert_eq!(stdout, [0, 255]);
+    }
+
+    #[test]
+    fn test_payload_written_to_out_file() {
+        let output = TestFile::new("payload-out");
+        let mut stdout = Vec::new();
+
+        write_payload_to(
+            &[0, 255],
+            &payload_args(Some(&output.0), false),
+            true,
+            &mut stdout,
+        )
+        .unwrap();
+
+        assert!(stdout.is_empty());
+        assert_eq!(fs::read(&output.0).unwrap(), [0, 255]);
+    }
+
+    #[test]
+    fn test_verify_pixels() {
+        let red = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        let blue = Png::solid_color(4, 4, [0, 0, 255]).unwrap();
+
+        assert!(verify_pixels(pixel_check(&red, true).unwrap(), &red).is_ok());
+        assert!(verify_pixels(pixel_check(&red, true).unwrap(), &blue).is_err());
+        assert!(verify_pixels(None, &blue).is_ok());
+    }
+
+    #[cfg(feature = "decoder-check")]
+    #[test]
+    fn test_verify_pixels_with_png_crate() {
+        let red = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        let mut moved = Png::from_chunks(red.chunks().to_vec());
+        moved.swap(1, 2);
+
+        let error = verify_pixels(pixel_check(&red, true).unwrap(), &moved).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with("The png crate can't decode the image"));
+    }
+
+    #[test]
+    fn test_print() {
+        let file = TestFile::new("print");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let args = PrintArgs {
+            file_path: file.0.clone(),
+            entropy: false,
+        };
+
+        assert_eq!(
+            print(args, &options()).unwrap(),
+            vec![
+                "0: IHDR, 13 bytes",
+                "1: IDAT, 25 bytes",
+                "2: ruSt, 14 bytes",
+                "3: IEND, 0 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_suggested_palette() {
+        let mut png = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        png.append_chunk(
+            SuggestedPalette {
+                name: String::from("web"),
+                sample_depth: 8,
+                entries: vec![SuggestedPaletteEntry {
+                    red: 255,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                    frequency: 1,
+                }],
+            }
+            .to_chunk()
+            .unwrap(),
+        );
+        let file = TestFile::with_png("print-splt", &png);
+
+        let args = PrintArgs {
+            file_path: file.0.clone(),
+            entropy: false,
+        };
+
+        assert_eq!(
+            print(args, &options()).unwrap()[2],
+            "2: sPLT, 11 bytes, palette \"web\" with 1 8-bit entries"
+        );
+    }
+
+    #[test]
+    fn test_print_entropy() {
+        let mut png = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            (0..=255).collect(),
+        ));
+        let file = TestFile::with_png("print-entropy", &png);
+
+        let args = PrintArgs {
+            file_path: file.0.clone(),
+            entropy: true,
+        };
+        let lines = print(args, &options()).unwrap();
+
+        assert_eq!(lines[0], "0: IHDR, 13 bytes, entropy 1.35 bits/byte");
+        assert_eq!(
+            lines[2],
+            "2: ruSt, 256 bytes, entropy 8.00 bits/byte (looks compressed or encrypted)"
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let file = TestFile::new("dump");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let args = DumpArgs {
+            file_path: file.0.clone(),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            payload: payload_args(None, false),
+        };
+        let dumped = dump(args, &options()).unwrap();
+
+        let chunk = Chunk::try_from(&dumped[..]).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "ruSt");
+        assert_eq!(chunk.data(), b"hidden message");
+        assert_eq!(dumped.len(), 14 + Chunk::METADATA_BYTES);
+    }
+
+    fn inject_args(file_path: &Path, chunk: &Path, position: Option<usize>) -> InjectArgs {
+        InjectArgs {
+            file_path: file_path.to_path_buf(),
+            chunk: chunk.to_path_buf(),
+            position,
+            trailing: TrailingArgs::default(),
+        }
+    }
+
+    #[test]
+    fn test_dump_then_inject() {
+        let source = TestFile::new("inject-source");
+        let target = TestFile::new("inject-target");
+        let chunk_file = TestFile::new("inject-chunk");
+        encode(encode_args(&source.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let args = DumpArgs {
+            file_path: source.0.clone(),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            payload: payload_args(None, false),
+        };
+        fs::write(&chunk_file.0, dump(args, &options()).unwrap()).unwrap();
+
+        inject(inject_args(&target.0, &chunk_file.0, Some(1)), &options()).unwrap();
+
+        let png = read_png(&target.0, &options()).unwrap();
+        assert!(png.chunks()[1].chunk_type().matches("ruSt"));
+        assert_eq!(png.chunks()[1].data(), b"hidden message");
+    }
+
+    #[test]
+    fn test_inject_appends_before_end() {
+        let file = TestFile::new("inject-append");
+        let chunk_file = TestFile::new("inject-append-chunk");
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hello".to_vec());
+        fs::write(&chunk_file.0, chunk.as_bytes()).unwrap();
+
+        assert_eq!(
+            inject(inject_args(&file.0, &chunk_file.0, None), &options()).unwrap(),
+            chunk
+        );
+
+        let png = read_png(&file.0, &options()).unwrap();
+        let chunks = png.chunks();
+        assert_eq!(chunks[chunks.len() - 2], chunk);
+    }
+
+    #[test]
+    fn test_inject_rejects_invalid_chunks() {
+        let file = TestFile::new("inject-invalid");
+        let chunk_file = TestFile::new("inject-invalid-chunk");
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hello".to_vec());
+
+        let mut bad_crc = chunk.as_bytes();
+        *bad_crc.last_mut().unwrap() ^= 0xFF;
+        fs::write(&chunk_file.0, &bad_crc).unwrap();
+        assert!(inject(inject_args(&file.0, &chunk_file.0, None), &options()).is_err());
+
+        let mut extra = chunk.as_bytes();
+        extra.push(0);
+        fs::write(&chunk_file.0, &extra).unwrap();
+        assert_eq!(
+            inject(inject_args(&file.0, &chunk_file.0, None), &options())
+                .unwrap_err()
+                .to_string(),
+            "The chunk file has 1 bytes after the chunk's CRC, but should hold a single chunk"
+        );
+
+        let critical = Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new());
+        fs::write(&chunk_file.0, critical.as_bytes()).unwrap();
+        assert!(inject(inject_args(&file.0, &chunk_file.0, None), &options()).is_err());
+
+        fs::write(&chunk_file.0, chunk.as_bytes()).unwrap();
+        assert!(inject(inject_args(&file.0, &chunk_file.0, Some(0)), &options()).is_err());
+    }
+
+    fn move_args(file_path: &Path, chunk_type: &str, position: usize) -> MoveArgs {
+        MoveArgs {
+            file_path: file_path.to_path_buf(),
+            chunk_type: ChunkType::from_str(chunk_type).unwrap(),
+            position,
+            trailing: TrailingArgs::default(),
+        }
+    }
+
+    #[test]
+    fn test_move() {
+        let file = TestFile::new("move");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+        let original = read_png(&file.0, &options()).unwrap();
+
+        move_chunk(move_args(&file.0, "ruSt", 1), &options()).unwrap();
+
+        let moved = read_png(&file.0, &options()).unwrap();
+        assert_eq!(moved.chunks()[1], original.chunks()[2]);
+        assert_eq!(moved.chunks()[2], original.chunks()[1]);
+        assert_eq!(moved.chunks().len(), original.chunks().len());
+    }
+
+    #[test]
+    fn test_move_rejects_invalid_positions() {
+        let file = TestFile::new("move-invalid");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+        let original = fs::read(&file.0).unwrap();
+
+        assert!(move_chunk(move_args(&file.0, "ruSt", 0), &options()).is_err());
+        assert!(move_chunk(move_args(&file.0, "ruSt", 4), &options()).is_err());
+        assert!(move_chunk(move_args(&file.0, "IEND", 1), &options()).is_err());
+
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+
+    #[test]
+    fn test_extract_trailing_data() {
+        let file = TestFile::new("extract-input");
+        let output = TestFile::new("extract-output");
+        let mut bytes = fs::read(&file.0).unwrap();
+        bytes.extend_from_slice(b"PK\x03\x04secret");
+        fs::write(&file.0, bytes).unwrap();
+
+        let args = ExtractArgs {
+            file_path: file.0.clone(),
+            trailing: Some(output.0.clone()),
+            xmp: false,
+        };
+
+        assert_eq!(
+            extract(args, &options()).unwrap(),
+            vec!["Extracted 10 bytes of ZIP data"]
+        );
+        assert_eq!(fs::read(&output.0).unwrap(), b"PK\x03\x04secret");
+    }
+
+    #[test]
+    fn test_extract_without_trailing_data() {
+        let file = TestFile::new("extract-none");
+        let args = ExtractArgs {
+            file_path: file.0.clone(),
+            trailing: Some(std::env::temp_dir().join("pngme-extract-none.bin")),
+            xmp: false,
+        };
+
+        assert!(extract(args, &options()).is_err());
+        assert!(!std::env::temp_dir().join("pngme-extract-none.bin").exists());
+    }
+
+    #[test]
+    fn test_encode_then_extract_xmp() {
+        let file = TestFile::new("xmp");
+        let packet = TestFile::new("xmp-packet");
+        fs::write(
+            &packet.0,
+            "<?xpacket begin=\"\"?><x:xmpmeta><rdf:RDF><rdf:Description>\
+             <dc:format>image/png</dc:format><xmp:Rating/></rdf:Description></rdf:RDF>\
+             </x:xmpmeta>\n   \n<?xpacket end=\"w\"?>",
+        )
+        .unwrap();
+
+        let mut args = encode_args(&file.0, "ruSt", "hidden message");
+        args.xmp_file = Some(packet.0.clone());
+        encode(args, &options()).unwrap();
+
+        let extracted = extract(
+            ExtractArgs {
+                file_path: file.0.clone(),
+                trailing: None,
+                xmp: true,
+            },
+            &options(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            extracted,
+            vec![
+                "<?xpacket begin=\"\"?>",
+                "<x:xmpmeta>",
+                "  <rdf:RDF>",
+                "    <rdf:Description>",
+                "      <dc:format>image/png</dc:format>",
+                "      <xmp:Rating/>",
+                "    </rdf:Description>",
+                "  </rdf:RDF>",
+                "</x:xmpmeta>",
+                "<?xpacket end=\"w\"?>",
+            ]
+        );
+        assert!(decode(decode_args(&file.0, "ruSt"), &options()).is_ok());
+    }
+
+    #[test]
+    fn test_extract_without_xmp() {
+        let file = TestFile::new("xmp-none");
+        let args = ExtractArgs {
+            file_path: file.0.clone(),
+            trailing: None,
+            xmp: true,
+        };
+
+        assert_eq!(
+            extract(args, &options()).unwrap_err().to_string(),
+            "The file has no XMP metadata"
+        );
+    }
+
+    /// Two frame animation of the solid colour carrier, the second frame sharing its image data
+    fn animated_png() -> Png {
+        #[rustfmt::skip]
+        let frame_control = |sequence: u8| vec![
+            0, 0, 0, sequence,
+            0, 0, 0, 4,
+            0, 0, 0, 4,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 1, 0, 10,
+            0, 0,
+        ];
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+
+        let carrier = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        let image_data = carrier.chunk_by_type("IDAT").unwrap().data().to_vec();
+        let frame_data = [&[0, 0, 0, 2], &image_data[..]].concat();
+
+        Png::from_chunks(vec![
+            carrier.chunk_by_type("IHDR").unwrap().clone(),
+            chunk("acTL", vec![0, 0, 0, 2, 0, 0, 0, 0]),
+            chunk("fcTL", frame_control(0)),
+            chunk("IDAT", image_data),
+            chunk("fcTL", frame_control(1)),
+            chunk("fdAT", frame_data),
+            chunk("IEND", Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_frames_list() {
+        let file = TestFile::with_png("frames-list", &animated_png());
+        let args = FramesArgs {
+            file_path: file.0.clone(),
+            extract: None,
+        };
+
+        assert_eq!(
+            frames(args, &options()).unwrap(),
+            vec![
+                "2 frames, looping forever",
+                "frame 0: 4x4 at (0, 0), delay 0.1s, dispose None, blend Source",
+                "frame 1: 4x4 at (0, 0), delay 0.1s, dispose None, blend Source",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frames_extract() {
+        let file = TestFile::with_png("frames", &animated_png());
+        let directory = std::env::temp_dir().join(format!("pngme-frames-{}", std::process::id()));
+        let args = FramesArgs {
+            file_path: file.0.clone(),
+            extract: Some(directory.clone()),
+        };
+
+        let written = frames(args, &options());
+        let frame_bytes: Vec<Vec<u8>> = written
+            .iter()
+            .flatten()
+            .map(|path| fs::read(path).unwrap())
+            .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let written = written.unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(
+            written[1],
+            directory.join("frame-001.png").display().to_string()
+        );
+        assert_eq!(frame_bytes[0], frame_bytes[1]);
+        assert!(Png::try_from(frame_bytes[1].as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_frames_not_animated() {
+        let file = TestFile::new("frames-not-animated");
+        let directory = std::env::temp_dir().join("pngme-frames-not-animated");
+        let args = FramesArgs {
+            file_path: file.0.clone(),
+            extract: Some(directory.clone()),
+        };
+
+        assert!(frames(args, &options()).is_err());
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn test_icc_insert_then_extract() {
+        let file = TestFile::new("icc");
+        let profile = TestFile::new("icc-profile");
+        let output = TestFile::new("icc-output");
+        fs::write(&profile.0, b"not really an ICC profile").unwrap();
+
+        icc(
+            IccArgs::Insert {
+                file_path: file.0.clone(),
+                profile_file: profile.0.clone(),
+                name: String::from("Test"),
+                trailing: TrailingArgs::default(),
+            },
+            &options(),
+        )
+        .unwrap();
+        icc(
+            IccArgs::Extract {
+                file_path: file.0.clone(),
+                output_file: output.0.clone(),
+            },
+            &options(),
+        )
+        .unwrap();
+
+        let embedded = read_png(&file.0, &options())
+            .unwrap()
+            .icc_profile()
+            .unwrap()
+            .unwrap();
+        assert_eq!(embedded.name, "Test");
+        assert_eq!(fs::read(&output.0).unwrap(), b"not really an ICC profile");
+    }
+
+    #[test]
+    fn test_icc_extract_without_profile() {
+        let file = TestFile::new("icc-none");
+        let args = IccArgs::Extract {
+            file_path: file.0.clone(),
+            output_file: std::env::temp_dir().join("pngme-icc-none.icc"),
+        };
+
+        assert_eq!(
+            icc(args, &options()).unwrap_err().to_string(),
+            "The file has no embedded ICC profile"
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let file = TestFile::new("annotate");
+        let set = |key: &str, value: &str| AnnotateArgs::Set {
+            file_path: file.0.clone(),
+            key: String::from(key),
+            value: String::from(value),
+            trailing: TrailingArgs::default(),
+        };
+        let list = |prefix: &str| AnnotateArgs::List {
+            file_path: file.0.clone(),
+            prefix: String::from(prefix),
+        };
+
+        annotate(set("case.number", "1234"), &options()).unwrap();
+        annotate(set("case.operator", "jdoe"), &options()).unwrap();
+        annotate(set("source", "camera"), &options()).unwrap();
+        annotate(set("case.number", "5678"), &options()).unwrap();
+
+        let value = annotate(
+            AnnotateArgs::Get {
+                file_path: file.0.clone(),
+                key: String::from("case.number"),
+            },
+            &options(),
+        )
+        .unwrap();
+
+        assert_eq!(value, vec!["5678"]);
+        assert_eq!(
+            annotate(list("case."), &options()).unwrap(),
+            vec!["case.number: 5678", "case.operator: jdoe"]
+        );
+        assert_eq!(annotate(list(""), &options()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_annotate_get_missing_key() {
+        let file = TestFile::new("annotate-missing");
+        let args = AnnotateArgs::Get {
+            file_path: file.0.clone(),
+            key: String::from("source"),
+        };
+
+        assert_eq!(
+            annotate(args, &options()).unwrap_err().to_string(),
+            "The file has no 'source' annotation"
+        );
+    }
+
+    #[test]
+    fn test_strip() {
+        let file = TestFile::new("strip");
+        encode(
+            encode_args(&file.0, "tIME", "\u{7}\u{e6}\u{1}\u{1}\0\0\0"),
+            &options(),
+        )
+        .unwrap();
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let removed = strip(
+            StripArgs {
+                file_path: file.0.clone(),
+                preset: StripPreset::Privacy,
+                trailing: TrailingArgs::default(),
+            },
+            &options(),
+        )
+        .unwrap();
+
+        assert_eq!(removed.len(), 1);
+        assert!(removed[0].chunk_type().matches("tIME"));
+        assert!(decode(decode_args(&file.0, "tIME"), &options()).is_err());
+        assert!(decode(decode_args(&file.0, "ruSt"), &options()).is_ok());
+    }
+
+    #[test]
+    fn test_anonymize() {
+        let file = TestFile::new("anonymize");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+        let mut bytes = fs::read(&file.0).unwrap();
+        bytes.extend_from_slice(b"appended");
+        fs::write(&file.0, bytes).unwrap();
+
+        anonymize(
+            AnonymizeArgs {
+                file_path: file.0.clone(),
+                output_file: None,
+                verify_pixels: true,
+            },
+            &options(),
+        )
+        .unwrap();
+
+        let png = read_png(&file.0, &options()).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert!(png.trailing_data().is_empty());
+    }
+
+    #[test]
+    fn test_new() {
+        let output = TestFile::new("new");
+        let args = |noise, gradient| NewArgs {
+            size: Some((3, 2)),
+            color: None,
+            noise,
+            gradient,
+            fit_payload: None,
+            out: output.0.clone(),
+        };
+
+        new(args(None, false)).unwrap();
+        let solid = read_png(&output.0, &options()).unwrap();
+        new(args(Some(Some(7)), false)).unwrap();
+        let noise = read_png(&output.0, &options()).unwrap();
+        new(args(None, true)).unwrap();
+        let gradient = read_png(&output.0, &options()).unwrap();
+
+        assert_eq!(solid.chunk_by_type("IHDR"), noise.chunk_by_type("IHDR"));
+        assert_eq!(solid.chunk_by_type("IHDR"), gradient.chunk_by_type("IHDR"));
+        assert_ne!(
+            solid.image_data_checksum().unwrap(),
+            noise.image_data_checksum().unwrap()
+        );
+        assert_ne!(
+            solid.image_data_checksum().unwrap(),
+            gradient.image_data_checksum().unwrap()
+        );
+        assert!(new(NewArgs {
+            size: Some((0, 2)),
+            ..args(None, false)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_new_fit_payload() {
+        let output = TestFile::new("new-fit");
+        let payload = TestFile::new("new-fit-payload");
+        fs::write(&payload.0, vec![0; 3000]).unwrap();
+
+        new(NewArgs {
+            size: None,
+            color: None,
+            noise: Some(None),
+            gradient: false,
+            fit_payload: Some(payload.0.clone()),
+            out: output.0.clone(),
+        })
+        .unwrap();
+
+        let header = read_png(&output.0, &options())
+            .unwrap()
+            .image_header()
+            .unwrap();
+        assert_eq!((header.width, header.height), (32, 32));
+
+        assert_eq!(fit_payload(0), (1, 1));
+        assert_eq!(fit_payload(300), (10, 10));
+        assert_eq!(fit_payload(301), (11, 11));
+        assert_eq!(
+            fit_payload(u64::MAX),
+            (Png::MAX_GENERATED_DIMENSION, Png::MAX_GENERATED_DIMENSION)
+        );
+    }
+
+    #[test]
+    fn test_selftest() {
+        let lines = selftest(SelftestArgs { file_path: None }, &options()).unwrap();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[1..].iter().all(|line| line.ends_with(": ok")));
+
+        let file = TestFile::new("selftest");
+        let original = fs::read(&file.0).unwrap();
+        let args = SelftestArgs {
+            file_path: Some(file.0.clone()),
+        };
+
+        assert!(selftest(args, &options()).is_ok());
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+
+    #[test]
+    fn test_unknown_external_command() {
+        let error = external(&[OsString::from("no-such-command"), OsString::from("a.png")]);
+
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "'no-such-command' isn't a pngme command, and there's no pngme-no-such-command on PATH"
+        );
+    }
+
+    #[test]
+    fn test_read_png_checks_size_first() {
+        let file = TestFile::new("read-too-large");
+        let size = fs::metadata(&file.0).unwrap().len() as usize;
+        let options = ParseOptions {
+            max_file_size: size - 1,
+            ..ParseOptions::default()
+        };
+
+        let error = read_png(&file.0, &options).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "The given source is {} bytes but at most {} are allowed",
+                size,
+                size - 1
+            )
+        );
+    }
+
+    #[test]
+    fn test_gzipped_files() {
+        let path = std::env::temp_dir().join(format!("pngme-gzip-{}.png.gz", std::process::id()));
+        let file = TestFile(path);
+        let png = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        write_png(&file.0, &png).unwrap();
+
+        assert_eq!(
+            FileType::sniff(&fs::read(&file.0).unwrap()),
+            Some(FileType::Gzip)
+        );
+
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+        let chunk = decode(decode_args(&file.0, "ruSt"), &options()).unwrap();
+        assert_eq!(chunk.data(), b"hidden message");
+
+        // a gzip file well under the limit can still decompress to far more than it
+        let mut large = Png::solid_color(4, 4, [255, 0, 0]).unwrap();
+        large.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            vec![b'a'; 1 << 16],
+        ));
+        write_png(&file.0, &large).unwrap();
+        let options = ParseOptions {
+            max_file_size: 4096,
+            ..ParseOptions::default()
+        };
+
+        assert_eq!(
+            read_png(&file.0, &options).err().unwrap().to_string(),
+            format!("{}: decompresses to more than 4096 bytes", file.0.display())
+        );
+    }
+
+    #[test]
+    fn test_read_png_with_lenient_crc() {
+        let file = TestFile::new("lenient-crc");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        // zero the CRC of the ruSt chunk, which is just before the 12 byte IEND chunk
+        let mut bytes = fs::read(&file.0).unwrap();
+        let crc_start = bytes.len() - 16;
+        bytes[crc_start..crc_start + 4].copy_from_slice(&[0; 4]);
+        fs::write(&file.0, bytes).unwrap();
+
+        let lenient = ParseOptions {
+            lenient_crc: true,
+            ..ParseOptions::default()
+        };
+        let png = read_png(&file.0, &lenient).unwrap();
+
+        assert!(read_png(&file.0, &options()).is_err());
+        assert_eq!(png.warnings().len(), 1);
+        assert!(png.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_read_png_with_lenient_truncation() {
+        let file = TestFile::new("lenient-truncation");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        // cut the file off part way through the ruSt chunk's data
+        let bytes = fs::read(&file.0).unwrap();
+        fs::write(&file.0, &bytes[..bytes.len() - 20]).unwrap();
+
+        let lenient = ParseOptions {
+            lenient_truncation: true,
+            ..ParseOptions::default()
+        };
+        let png = read_png(&file.0, &lenient).unwrap();
+
+        assert!(read_png(&file.0, &options()).is_err());
+        assert_eq!(
+            png.warnings(),
+            &[ParseWarning::Truncated(png.chunks().len() - 1)]
+        );
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden mes");
+    }
+
+    #[test]
+    fn test_io_errors_name_the_file() {
+        let path = std::env::temp_dir().join("pngme-missing-file.png");
+
+        let error = decode(decode_args(&path, "ruSt"), &options())
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}: ", path.display())));
+    }
+
+    #[test]
+    fn test_decode_suggests_similar_chunk_types() {
+        let file = TestFile::new("decode-suggests");
+        encode(encode_args(&file.0, "ruSt", "hidden message"), &options()).unwrap();
+
+        let error = decode(decode_args(&file.0, "rust"), &options()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Unknown chunk type 'rust'. Did you mean 'ruSt'? Chunk types are case-sensitive"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Display;
+
+/// File formats which can be recognised from their leading magic bytes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FileType {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Webp,
+    Zip,
+    Pdf,
+    Gzip,
+    SevenZip,
+    Rar,
+}
+
+impl FileType {
+    const SIGNATURES: [(FileType, &'static [u8]); 11] = [
+        (FileType::Png, &[137, 80, 78, 71, 13, 10, 26, 10]),
+        (FileType::Jpeg, &[0xFF, 0xD8, 0xFF]),
+        (FileType::Gif, b"GIF87a"),
+        (FileType::Gif, b"GIF89a"),
+        (FileType::Bmp, b"BM"),
+        (FileType::Zip, b"PK\x03\x04"),
+        (FileType::Zip, b"PK\x05\x06"),
+        (FileType::Pdf, b"%PDF-"),
+        (FileType::Gzip, &[0x1F, 0x8B]),
+        (FileType::SevenZip, &[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]),
+        (FileType::Rar, b"Rar!\x1A\x07"),
+    ];
+
+    /// Guess the type of the given bytes from their magic number
+    pub fn sniff(bytes: &[u8]) -> Option<FileType> {
+        // WebP is a RIFF container, so the magic is split around the container size
+        if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            return Some(FileType::Webp);
+        }
+
+        FileType::SIGNATURES
+            .iter()
+            .find(|(_, signature)| bytes.starts_with(signature))
+            .map(|(file_type, _)| *file_type)
+    }
+
+    /// Formats whose readers would also accept the given bytes even though they don't start with
+    /// that format's magic number. PDF readers search for the `%PDF-` header anywhere near the
+    /// start of a file and ZIP readers search backwards for the end of central directory record,
+    /// so either can be hidden inside another file to make a polyglot
+    pub fn find_embedded(bytes: &[u8]) -> Vec<FileType> {
+        let mut found = Vec::new();
+
+        if contains(bytes, b"%PDF-") {
+            found.push(FileType::Pdf);
+        }
+
+        if contains(bytes, b"PK\x05\x06") {
+            found.push(FileType::Zip);
+        }
+
+        found
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+impl Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FileType::Png => "PNG",
+            FileType::Jpeg => "JPEG",
+            FileType::Gif => "GIF",
+            FileType::Bmp => "BMP",
+            FileType::Webp => "WebP",
+            FileType::Zip => "ZIP",
+            FileType::Pdf => "PDF",
+            FileType::Gzip => "gzip",
+            FileType::SevenZip => "7-Zip",
+            FileType::Rar => "RAR",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_known_types() {
+        assert_eq!(
+            FileType::sniff(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(FileType::Jpeg)
+        );
+        assert_eq!(FileType::sniff(b"GIF89a\x01\x00"), Some(FileType::Gif));
+        assert_eq!(FileType::sniff(b"PK\x03\x04\x14\x00"), Some(FileType::Zip));
+        assert_eq!(FileType::sniff(b"%PDF-1.7\n"), Some(FileType::Pdf));
+        assert_eq!(
+            FileType::sniff(b"RIFF\x24\x00\x00\x00WEBPVP8 "),
+            Some(FileType::Webp)
+        );
+    }
+
+    #[test]
+    fn test_find_embedded() {
+        let bytes = b"Comment\0junk %PDF-1.4 more junk PK\x05\x06\0\0";
+        assert_eq!(
+            FileType::find_embedded(bytes),
+            vec![FileType::Pdf, FileType::Zip]
+        );
+        assert!(FileType::find_embedded(b"plain text").is_empty());
+    }
+
+    #[test]
+    fn test_sniff_unknown_type() {
+        assert_eq!(FileType::sniff(b"hello world"), None);
+        assert_eq!(FileType::sniff(&[]), None);
+    }
+}
@@ -0,0 +1,14 @@
+pub mod annotation;
+pub mod chunk;
+pub mod chunk_data;
+pub mod chunk_type;
+pub mod file_type;
+pub mod parse_options;
+pub mod png;
+pub mod strip;
+
+/// Generic PNGme error
+pub type Error = Box<dyn std::error::Error>;
+
+/// Generic PNGme result
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,15 +1,79 @@
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
 
-/// Generic PNGme error
-pub type Error = Box<dyn std::error::Error>;
+use args::{Command, PngMeArgs};
+use pngme::Result;
+use structopt::StructOpt;
 
-/// Generic PNGme result
-pub type Result<T> = std::result::Result<T, Error>;
+fn print_lines(lines: Vec<String>) -> Result<()> {
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn run(args: PngMeArgs) -> Result<()> {
+    let options = args.parse.options();
+
+    match args.command {
+        Command::Encode(args) => {
+            println!("{}", commands::encode(args, &options)?);
+            Ok(())
+        }
+        Command::Decode(args) => {
+            let payload = args.payload.clone();
+            commands::write_payload(commands::decode(args, &options)?.data(), &payload)
+        }
+        Command::Remove(args) if args.chunk_type.is_some() => {
+            let payload = args.payload.clone();
+            let removed = commands::remove(args, &options)?;
+            commands::write_payload(removed[0].data(), &payload)
+        }
+        Command::Remove(args) => {
+            for chunk in commands::remove(args, &options)? {
+                println!("{}", chunk.chunk_type());
+            }
+            Ok(())
+        }
+        Command::Print(args) => print_lines(commands::print(args, &options)?),
+        Command::Dump(args) => {
+            let payload = args.payload.clone();
+            commands::write_payload(&commands::dump(args, &options)?, &payload)
+        }
+        Command::Inject(args) => {
+            let chunk = commands::inject(args, &options)?;
+            println!(
+                "Inserted {} chunk of {} bytes",
+                chunk.chunk_type(),
+                chunk.length()
+            );
+            Ok(())
+        }
+        Command::Move(args) => commands::move_chunk(args, &options),
+        Command::Extract(args) => print_lines(commands::extract(args, &options)?),
+        Command::Frames(args) => print_lines(commands::frames(args, &options)?),
+        Command::Icc(args) => commands::icc(args, &options),
+        Command::Annotate(args) => print_lines(commands::annotate(args, &options)?),
+        Command::Anonymize(args) => commands::anonymize(args, &options),
+        Command::New(args) => commands::new(args),
+        Command::Selftest(args) => print_lines(commands::selftest(args, &options)?),
+        Command::External(args) => match commands::external(&args)? {
+            0 => Ok(()),
+            code => std::process::exit(code),
+        },
+        Command::Strip(args) => {
+            for chunk in commands::strip(args, &options)? {
+                println!("{}", chunk.chunk_type());
+            }
+            Ok(())
+        }
+    }
+}
 
-fn main() -> Result<()> {
-    todo!();
+fn main() {
+    // report errors with their Display message rather than the Debug output main would print
+    if let Err(e) = run(PngMeArgs::from_args()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,50 @@
+/// Limits applied when parsing untrusted PNG data, so that a malicious file can't make the
+/// parser do unbounded work, and leniency settings for files from sloppy writers
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// Largest accepted file, in bytes
+    pub max_file_size: usize,
+
+    /// Largest accepted number of chunks in a single file
+    pub max_chunks: usize,
+
+    /// Largest accepted data length of a single chunk, in bytes
+    pub max_chunk_length: usize,
+
+    /// Largest accepted size of compressed chunk data (e.g. iCCP or iTXt) once inflated, in bytes
+    pub max_inflated_length: usize,
+
+    /// Largest accepted ratio of inflated to compressed size, so a small crafted chunk can't
+    /// expand to exhaust memory even when it's under the overall ceiling
+    pub max_inflate_ratio: usize,
+
+    /// Accept chunks whose CRC is an all-zero placeholder instead of rejecting the file. The
+    /// correct CRC is always written back out when the chunk is serialized
+    pub lenient_crc: bool,
+
+    /// Keep whatever data is available for a final chunk which runs past the end of the file,
+    /// e.g. from a truncated download, instead of rejecting the whole file
+    pub lenient_truncation: bool,
+}
+
+impl ParseOptions {
+    pub const DEFAULT_MAX_FILE_SIZE: usize = 256 * 1024 * 1024;
+    pub const DEFAULT_MAX_CHUNKS: usize = 100_000;
+    pub const DEFAULT_MAX_CHUNK_LENGTH: usize = 64 * 1024 * 1024;
+    pub const DEFAULT_MAX_INFLATED_LENGTH: usize = 16 * 1024 * 1024;
+    pub const DEFAULT_MAX_INFLATE_RATIO: usize = 100;
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: ParseOptions::DEFAULT_MAX_FILE_SIZE,
+            max_chunks: ParseOptions::DEFAULT_MAX_CHUNKS,
+            max_chunk_length: ParseOptions::DEFAULT_MAX_CHUNK_LENGTH,
+            max_inflated_length: ParseOptions::DEFAULT_MAX_INFLATED_LENGTH,
+            max_inflate_ratio: ParseOptions::DEFAULT_MAX_INFLATE_RATIO,
+            lenient_crc: false,
+            lenient_truncation: false,
+        }
+    }
+}
@@ -1,238 +1,1845 @@
-use crate::chunk::Chunk;
+use crate::annotation::Annotations;
+use crate::chunk::{Chunk, ChunkError};
+use crate::chunk_data::{
+    Animation, AnimationControl, Background, ChunkDataError, ColorType, FrameControl, Histogram,
+    IccProfile, ImageHeader, InternationalText, Palette, SuggestedPalette, Transparency,
+};
+use crate::chunk_type::ChunkType;
+use crate::file_type::FileType;
+use crate::parse_options::ParseOptions;
+use crate::strip::StripPreset;
 use crate::{Error, Result};
-use std::{convert::TryFrom, fmt::Display};
+use crc::{crc32, Hasher32};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    convert::TryFrom,
+    fmt::Display,
+    io::{Read, Write},
+    iter::FromIterator,
+    str::FromStr,
+};
 
 pub struct Png {
     chunks: Vec<Chunk>,
+    warnings: Vec<ParseWarning>,
+    trailing_data: Vec<u8>,
+
+    /// Options the file was parsed with, also applied when decoding compressed chunk data
+    options: ParseOptions,
 }
 
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+    /// Chunk type holding the compressed image data
+    pub const IMAGE_DATA_CHUNK_TYPE: &'static str = "IDAT";
+
+    /// Size IDAT data is split into when normalizing, matching libpng's default
+    pub const IMAGE_DATA_CHUNK_SIZE: usize = 8192;
+
+    /// Chunk type marking the image as using the sRGB colour space
+    pub const STANDARD_RGB_CHUNK_TYPE: &'static str = "sRGB";
+
+    /// Chunk type marking the end of the PNG datastream
+    pub const END_CHUNK_TYPE: &'static str = "IEND";
+
+    /// Largest width or height of a generated carrier image, which bounds the memory used
+    pub const MAX_GENERATED_DIMENSION: u32 = 8192;
+
+    /// Relative entropy above which chunk data looks compressed or encrypted
+    pub const HIGH_ENTROPY: f64 = 0.875;
+
+    /// Shortest chunk data whose entropy is worth judging. Below this, even short text uses
+    /// mostly distinct bytes and so looks random
+    pub const MIN_ENTROPY_LENGTH: usize = 16;
+
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        Self {
+            chunks,
+            warnings: Vec::new(),
+            trailing_data: Vec::new(),
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Collect chunks into a PNG, checking that it starts with IHDR and ends with IEND. Unlike
+    /// `collect`, which accepts any chunks so that partial files can be built up, the result is
+    /// always a well-formed file
+    pub fn try_from_iter<T: IntoIterator<Item = Chunk>>(iter: T) -> Result<Self> {
+        let png: Png = iter.into_iter().collect();
+        let first = png.chunks.first().map(Chunk::chunk_type);
+        let last = png.chunks.last().map(Chunk::chunk_type);
+
+        if !first.is_some_and(|t| t.matches(ImageHeader::CHUNK_TYPE)) {
+            return Err(Box::from(PngError::MisplacedChunk(
+                ImageHeader::CHUNK_TYPE,
+                "first",
+            )));
+        }
+
+        if !last.is_some_and(|t| t.matches(Png::END_CHUNK_TYPE)) {
+            return Err(Box::from(PngError::MisplacedChunk(
+                Png::END_CHUNK_TYPE,
+                "last",
+            )));
+        }
+
+        Ok(png)
+    }
+
+    /// Generate a carrier image of a single colour, for when there's no suitable image at hand
+    pub fn solid_color(width: u32, height: u32, color: [u8; 3]) -> Result<Self> {
+        Png::generate(width, height, |_, pixels| {
+            for pixel in pixels.chunks_mut(3) {
+                pixel.copy_from_slice(&color);
+            }
+        })
+    }
+
+    /// Generate a carrier image which fades from black at the top left to red along the top,
+    /// green down the left and white at the bottom right. Smooth like a photo's sky, so its image
+    /// data compresses like one rather than being flat or pure noise
+    pub fn gradient(width: u32, height: u32) -> Result<Self> {
+        let scale = |i: usize, n: u32| (i * 255 / (n as usize - 1).max(1)) as u8;
+
+        Png::generate(width, height, |y, pixels| {
+            for (x, pixel) in pixels.chunks_mut(3).enumerate() {
+                let (red, green) = (scale(x, width), scale(y, height));
+                pixel.copy_from_slice(&[red, green, red.min(green)]);
+            }
+        })
+    }
+
+    /// Generate a carrier image of random noise. Unlike a flat colour, its image data is as
+    /// high-entropy as any payload hidden alongside it. The same seed gives the same image
+    pub fn noise(width: u32, height: u32, seed: u64) -> Result<Self> {
+        let mut state = seed;
+
+        Png::generate(width, height, |_, pixels| {
+            for block in pixels.chunks_mut(8) {
+                let random = splitmix64(&mut state).to_le_bytes();
+                block.copy_from_slice(&random[..block.len()]);
+            }
+        })
+    }
+
+    /// Build an 8-bit RGB image, filling in the pixels of each scanline in turn given its row
+    fn generate<F>(width: u32, height: u32, mut fill: F) -> Result<Self>
+    where
+        F: FnMut(usize, &mut [u8]),
+    {
+        let valid = 1..=Png::MAX_GENERATED_DIMENSION;
+        if !valid.contains(&width) || !valid.contains(&height) {
+            return Err(Box::from(ChunkDataError::InvalidValue(
+                ImageHeader::CHUNK_TYPE,
+                "dimensions",
+            )));
+        }
+
+        let header = ImageHeader {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+
+        // every scanline is the 'None' filter type followed by the raw pixels
+        let mut scanline = vec![0; 1 + width as usize * 3];
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        for y in 0..height as usize {
+            fill(y, &mut scanline[1..]);
+            encoder.write_all(&scanline)?;
+        }
+
+        let mut png = Png::from_chunks(vec![
+            header.to_chunk(),
+            Chunk::new(
+                ChunkType::from_str(Png::IMAGE_DATA_CHUNK_TYPE)?,
+                encoder.finish()?,
+            ),
+            Chunk::new(ChunkType::from_str(Png::END_CHUNK_TYPE)?, Vec::new()),
+        ]);
+        png.rechunk_image_data(Png::IMAGE_DATA_CHUNK_SIZE)?;
+
+        Ok(png)
+    }
+
+    /// Parse a PNG file, enforcing the given limits
+    pub fn parse_with_options(value: &[u8], options: &ParseOptions) -> Result<Self> {
+        if value.len() > options.max_file_size {
+            return Err(Box::from(PngError::TooLarge(
+                value.len(),
+                options.max_file_size,
+            )));
+        }
+
+        if value.len() < Png::STANDARD_HEADER.len() {
+            return Err(Box::from(PngError::TooSmall));
+        }
+
+        // TODO: there's got to be a nicer way than advancing a pointer and reslicing each time
+        let mut index = 0;
+
+        // validate header
+        let header = &value[index..index + Png::STANDARD_HEADER.len()];
+        index += Png::STANDARD_HEADER.len();
+
+        if Png::STANDARD_HEADER != header {
+            let mut actual = [0; 8];
+            actual.copy_from_slice(header);
+            return Err(Box::from(PngError::InvalidHeader(
+                actual,
+                FileType::sniff(value),
+            )));
+        }
+
+        // parse one chunk at a time
+        let mut chunks = Vec::new();
+        let mut warnings = Vec::new();
+
+        while index < value.len() {
+            if chunks.len() == options.max_chunks {
+                return Err(Box::from(PngError::TooManyChunks(options.max_chunks)));
+            }
+
+            let bytes = &value[index..];
+            let chunk = match Chunk::parse_with_options(bytes, options) {
+                Ok(chunk) => chunk,
+                Err(e) if options.lenient_truncation && is_truncated(&e) => {
+                    let chunk = Chunk::parse_truncated(bytes).map_err(|_| e)?;
+                    warnings.push(ParseWarning::Truncated(chunks.len()));
+                    chunks.push(chunk);
+                    index = value.len();
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            let chunk_end = chunk.length() + Chunk::METADATA_BYTES;
+            index += chunk_end;
+
+            // lenient parsing lets placeholder CRCs through, but they're still worth reporting
+            let stored_crc = &bytes[chunk_end - Chunk::CRC_BYTES..chunk_end];
+            if stored_crc == [0, 0, 0, 0] && chunk.crc() != 0 {
+                warnings.push(ParseWarning::PlaceholderCrc(chunks.len()));
+            }
+
+            let is_end = chunk.chunk_type().matches(Png::END_CHUNK_TYPE);
+            chunks.push(chunk);
+
+            // anything after IEND isn't part of the PNG, but is kept so it isn't silently lost
+            if is_end {
+                break;
+            }
+        }
+
+        Ok(Png {
+            chunks,
+            warnings,
+            trailing_data: value[index..].to_vec(),
+            options: options.clone(),
+        })
+    }
+
+    /// Problems which were tolerated while parsing the file
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Bytes found after the IEND chunk
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+
+    /// Best guess at the format of any trailing data, e.g. an appended ZIP archive
+    pub fn trailing_data_type(&self) -> Option<FileType> {
+        FileType::sniff(&self.trailing_data)
+    }
+
+    /// Remove any trailing data so that it isn't written back out, returning it
+    pub fn strip_trailing_data(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailing_data)
+    }
+
+    /// Frames of an animated PNG, or `None` if the file isn't animated
+    pub fn animation(&self) -> Result<Option<Animation>> {
+        Animation::from_chunks(&self.chunks)
+    }
+
+    /// Palette histogram, validated against the palette
+    pub fn histogram(&self) -> Result<Option<Histogram>> {
+        let histogram = match self.chunk_by_type(Histogram::CHUNK_TYPE) {
+            Some(chunk) => Histogram::try_from(chunk)?,
+            None => return Ok(None),
+        };
+
+        let palette = self
+            .palette()?
+            .ok_or(PngError::MissingChunk(Palette::CHUNK_TYPE))?;
+        histogram.validate(&palette)?;
+
+        Ok(Some(histogram))
+    }
+
+    /// Suggested palettes, in file order. Unlike most chunks, sPLT may appear more than once
+    pub fn suggested_palettes(&self) -> Result<Vec<SuggestedPalette>> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().matches(SuggestedPalette::CHUNK_TYPE))
+            .map(SuggestedPalette::try_from)
+            .collect()
+    }
+
+    /// Background colour, validated against the image header and palette
+    pub fn background(&self) -> Result<Option<Background>> {
+        let chunk = match self.chunk_by_type(Background::CHUNK_TYPE) {
+            Some(chunk) => chunk,
+            None => return Ok(None),
+        };
+
+        let header = self.image_header()?;
+        let background = Background::parse(chunk, header.color_type)?;
+        background.validate(&header, self.palette()?.as_ref())?;
+
+        Ok(Some(background))
+    }
+
+    /// Transparency, validated against the image header and palette
+    pub fn transparency(&self) -> Result<Option<Transparency>> {
+        let chunk = match self.chunk_by_type(Transparency::CHUNK_TYPE) {
+            Some(chunk) => chunk,
+            None => return Ok(None),
+        };
+
+        let header = self.image_header()?;
+        let transparency = Transparency::parse(chunk, header.color_type)?;
+        transparency.validate(&header, self.palette()?.as_ref())?;
+
+        Ok(Some(transparency))
+    }
+
+    /// Embedded ICC colour profile
+    pub fn icc_profile(&self) -> Result<Option<IccProfile>> {
+        self.chunk_by_type(IccProfile::CHUNK_TYPE)
+            .map(|c| IccProfile::parse_with_options(c, &self.options))
+            .transpose()
+    }
+
+    /// Embed an ICC colour profile, replacing any existing one. The spec requires it to come
+    /// before PLTE and IDAT, so it's placed straight after IHDR. Any sRGB chunk is removed, as
+    /// the spec says it shouldn't be present alongside an embedded profile
+    pub fn set_icc_profile(&mut self, profile: &IccProfile) -> Result<()> {
+        let chunk = profile.to_chunk()?;
+        self.chunks
+            .retain(|c| !c.chunk_type().matches(Png::STANDARD_RGB_CHUNK_TYPE));
+
+        match self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().matches(IccProfile::CHUNK_TYPE))
+        {
+            Some(index) => self.chunks[index] = chunk,
+            None => {
+                let index = self
+                    .chunks
+                    .iter()
+                    .position(|c| c.chunk_type().matches(ImageHeader::CHUNK_TYPE))
+                    .ok_or(PngError::MissingChunk(ImageHeader::CHUNK_TYPE))?;
+                self.chunks.insert(index + 1, chunk);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// XMP metadata packet stored in an iTXt chunk
+    pub fn xmp(&self) -> Result<Option<String>> {
+        self.chunks
+            .iter()
+            .find(|c| InternationalText::has_keyword(c, InternationalText::XMP_KEYWORD))
+            .map(|c| InternationalText::parse_with_options(c, &self.options).map(|t| t.text))
+            .transpose()
+    }
+
+    /// Store an XMP metadata packet, replacing any existing one
+    pub fn set_xmp(&mut self, packet: &str) -> Result<()> {
+        let chunk = InternationalText::xmp(packet).to_chunk()?;
+
+        let existing = self
+            .chunks
+            .iter()
+            .position(|c| InternationalText::has_keyword(c, InternationalText::XMP_KEYWORD));
+
+        match existing {
+            Some(index) => self.chunks[index] = chunk,
+            None => self.append_chunk(chunk),
+        }
+
+        Ok(())
+    }
+
+    pub fn international_text_chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().matches(InternationalText::CHUNK_TYPE))
+    }
+
+    pub fn image_header(&self) -> Result<ImageHeader> {
+        let chunk = self
+            .chunk_by_type(ImageHeader::CHUNK_TYPE)
+            .ok_or(PngError::MissingChunk(ImageHeader::CHUNK_TYPE))?;
+        ImageHeader::try_from(chunk)
+    }
+
+    pub fn palette(&self) -> Result<Option<Palette>> {
+        self.chunk_by_type(Palette::CHUNK_TYPE)
+            .map(Palette::try_from)
+            .transpose()
+    }
+
+    /// Reconstruct a frame of an animated PNG as a standalone PNG, using the frame's dimensions
+    /// and turning its fdAT chunks back into IDAT
+    pub fn extract_frame(&self, index: usize) -> Result<Png> {
+        let animation = self.animation()?.ok_or(PngError::NotAnimated)?;
+        let frame = animation
+            .frames
+            .get(index)
+            .ok_or(PngError::UnknownFrame(index))?;
+
+        let mut header = self.image_header()?;
+        header.width = frame.control.width;
+        header.height = frame.control.height;
+
+        let mut chunks = vec![header.to_chunk()];
+
+        // the palette and ancillary chunks before the image data apply to every frame
+        chunks.extend(
+            self.chunks
+                .iter()
+                .take_while(|c| !Png::is_image_data(c))
+                .filter(|c| {
+                    let chunk_type = c.chunk_type();
+                    !chunk_type.matches(ImageHeader::CHUNK_TYPE)
+                        && !chunk_type.matches(AnimationControl::CHUNK_TYPE)
+                        && !chunk_type.matches(FrameControl::CHUNK_TYPE)
+                })
+                .cloned(),
+        );
+
+        for &data_index in &frame.data_chunks {
+            let chunk = &self.chunks[data_index];
+
+            if chunk.chunk_type().matches(Png::IMAGE_DATA_CHUNK_TYPE) {
+                chunks.push(chunk.clone());
+                continue;
+            }
+
+            // fdAT is IDAT prefixed with a sequence number
+            if chunk.length() < 4 {
+                return Err(Box::from(ChunkDataError::InvalidLength(
+                    Animation::FRAME_DATA_CHUNK_TYPE,
+                    chunk.length(),
+                )));
+            }
+
+            chunks.push(Chunk::new(
+                ChunkType::from_str(Png::IMAGE_DATA_CHUNK_TYPE)?,
+                chunk.data()[4..].to_vec(),
+            ));
+        }
+
+        chunks.push(Chunk::new(
+            ChunkType::from_str(Png::END_CHUNK_TYPE)?,
+            Vec::new(),
+        ));
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    fn is_image_data(chunk: &Chunk) -> bool {
+        let chunk_type = chunk.chunk_type();
+        chunk_type.matches(Png::IMAGE_DATA_CHUNK_TYPE)
+            || chunk_type.matches(Animation::FRAME_DATA_CHUNK_TYPE)
+    }
+
+    /// Other file formats embedded in the chunks or trailing data which would make this file a
+    /// polyglot, i.e. simultaneously valid as a PNG and something else
+    pub fn embedded_formats(&self) -> Vec<Embedded> {
+        let in_chunks = self.chunks.iter().enumerate().flat_map(|(index, chunk)| {
+            FileType::find_embedded(chunk.data())
+                .into_iter()
+                .map(move |file_type| Embedded::Chunk(index, file_type))
+        });
+
+        let mut in_trailing_data = FileType::find_embedded(&self.trailing_data);
+        if let Some(file_type) = self.trailing_data_type() {
+            if !in_trailing_data.contains(&file_type) {
+                in_trailing_data.insert(0, file_type);
+            }
+        }
+
+        in_chunks
+            .chain(in_trailing_data.into_iter().map(Embedded::TrailingData))
+            .collect()
+    }
+
+    /// Add a chunk at the end of the file, but before IEND so that decoders still see it
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        let end = self.end_index();
+        self.chunks.insert(end, chunk);
+    }
+
+    /// Insert a chunk at the given index, shifting later chunks along. IHDR has to stay first and
+    /// IEND last, so the index must be between 1 and that of IEND
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        let end = self.end_index();
+
+        if index == 0 || index > end {
+            return Err(Box::from(PngError::InvalidPosition(index, end)));
+        }
+
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Index new chunks are inserted at: that of IEND, or the end if there isn't one. Anything
+    /// after IEND would be treated as trailing data when the file is read back
+    fn end_index(&self) -> usize {
+        self.chunks
+            .iter()
+            .rposition(|c| c.chunk_type().matches(Png::END_CHUNK_TYPE))
+            .unwrap_or(self.chunks.len())
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let index = self.position_of(chunk_type)?;
+        let removed = self.chunks.remove(index);
+        Ok(removed)
+    }
+
+    /// Replace the first chunk of the given type in place, returning the chunk it replaced
+    pub fn replace_chunk(&mut self, chunk_type: &str, chunk: Chunk) -> Result<Chunk> {
+        let index = self.position_of(chunk_type)?;
+        Ok(std::mem::replace(&mut self.chunks[index], chunk))
+    }
+
+    /// Keep only the chunks for which the predicate returns true
+    pub fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&Chunk) -> bool,
+    {
+        self.chunks.retain(predicate)
+    }
+
+    /// Swap the chunks at the given indices. Panics if either index is out of bounds
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.chunks.swap(a, b)
+    }
+
+    /// Index of the first chunk of the given type, with suggestions if there isn't one
+    fn position_of(&self, chunk_type: &str) -> Result<usize> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().matches(chunk_type))
+            .ok_or_else(|| {
+                PngError::UnknownChunkType(
+                    chunk_type.to_string(),
+                    self.similar_chunk_types(chunk_type),
+                    self.private_chunk_types(),
+                )
+            })?;
+        Ok(index)
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
+        &Png::STANDARD_HEADER
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunks
+            .iter()
+            .find(|&c| c.chunk_type().matches(chunk_type))
+    }
+
+    /// First chunk of the given type, or an error suggesting similar types if there isn't one
+    pub fn find_chunk(&self, chunk_type: &str) -> Result<&Chunk> {
+        let index = self.position_of(chunk_type)?;
+        Ok(&self.chunks[index])
+    }
+
+    pub fn chunk_mut(&mut self, chunk_type: &str) -> Option<&mut Chunk> {
+        self.chunks
+            .iter_mut()
+            .find(|c| c.chunk_type().matches(chunk_type))
+    }
+
+    /// Provenance annotations attached to the file, empty if there aren't any
+    pub fn annotations(&self) -> Result<Annotations> {
+        match self.chunk_by_type(Annotations::CHUNK_TYPE) {
+            Some(chunk) => Annotations::try_from(chunk),
+            None => Ok(Annotations::new()),
+        }
+    }
+
+    /// Replace the provenance annotations attached to the file, keeping the position of any
+    /// existing annotation chunk
+    pub fn set_annotations(&mut self, annotations: &Annotations) {
+        let chunk = annotations.to_chunk();
+
+        match self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().matches(Annotations::CHUNK_TYPE))
+        {
+            Some(index) => self.chunks[index] = chunk,
+            None => self.append_chunk(chunk),
+        }
+    }
+
+    /// Remove the chunks selected by the preset, returning them
+    pub fn strip(&mut self, preset: StripPreset) -> Vec<Chunk> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| preset.removes(c));
+        self.chunks = kept;
+        removed
+    }
+
+    /// Remove everything not needed to decode the image, including trailing data, put the
+    /// remaining header chunks in a fixed order and split the image data into fixed size IDAT
+    /// chunks, so the output can't be fingerprinted back to the tool which produced it
+    pub fn anonymize(&mut self) -> Result<()> {
+        self.strip(StripPreset::Minimal);
+        self.trailing_data.clear();
+
+        // the only chunks left which can move around are those before the image data
+        let header_length = self
+            .chunks
+            .iter()
+            .position(|c| Png::is_image_data(c) || c.chunk_type().matches(FrameControl::CHUNK_TYPE))
+            .unwrap_or(self.chunks.len());
+
+        let order = [
+            ImageHeader::CHUNK_TYPE,
+            AnimationControl::CHUNK_TYPE,
+            Palette::CHUNK_TYPE,
+            Transparency::CHUNK_TYPE,
+        ];
+        self.chunks[..header_length].sort_by_key(|c| {
+            order
+                .iter()
+                .position(|&chunk_type| c.chunk_type().matches(chunk_type))
+                .unwrap_or(order.len())
+        });
+
+        self.rechunk_image_data(Png::IMAGE_DATA_CHUNK_SIZE)
+    }
+
+    /// Merge each run of consecutive IDAT chunks and split it again into chunks of the given size
+    pub fn rechunk_image_data(&mut self, chunk_size: usize) -> Result<()> {
+        if chunk_size == 0 {
+            return Err(Box::from(PngError::InvalidChunkSize));
+        }
+
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut image_data = Vec::new();
+
+        // a trailing None flushes the final run of image data
+        for chunk in std::mem::take(&mut self.chunks)
+            .into_iter()
+            .map(Some)
+            .chain(std::iter::once(None))
+        {
+            if let Some(chunk) = &chunk {
+                if chunk.chunk_type().matches(Png::IMAGE_DATA_CHUNK_TYPE) {
+                    image_data.extend_from_slice(chunk.data());
+                    continue;
+                }
+            }
+
+            for data in image_data.chunks(chunk_size) {
+                chunks.push(Chunk::new(
+                    ChunkType::from_str(Png::IMAGE_DATA_CHUNK_TYPE)?,
+                    data.to_vec(),
+                ));
+            }
+
+            image_data.clear();
+            chunks.extend(chunk);
+        }
+
+        self.chunks = chunks;
+        Ok(())
+    }
+
+    /// Ancillary chunks, with their index, whose data looks compressed or encrypted rather than
+    /// text. Entropy is judged relative to the data length so that short payloads can still be
+    /// flagged. Image data is expected to look like that so is never included
+    pub fn high_entropy_chunks(&self) -> impl Iterator<Item = (usize, &Chunk)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.chunk_type().is_critical() && !Png::is_image_data(c))
+            .filter(|(_, c)| c.length() >= Png::MIN_ENTROPY_LENGTH)
+            .filter(|(_, c)| c.relative_entropy() > Png::HIGH_ENTROPY)
+    }
+
+    /// Checksum of the decompressed image data, used to verify that an edit only touched metadata.
+    /// Re-chunking or recompressing the image data leaves it unchanged, but any change to the
+    /// scanlines won't
+    pub fn image_data_checksum(&self) -> Result<u32> {
+        let compressed: Vec<u8> = self
+            .chunks
+            .iter()
+            .filter(|c| c.chunk_type().matches(Png::IMAGE_DATA_CHUNK_TYPE))
+            .flat_map(|c| c.data().iter().copied())
+            .collect();
+
+        let mut decoder = ZlibDecoder::new(&compressed[..]);
+        let mut digest = crc32::Digest::new(crc32::IEEE);
+        let mut buffer = [0; Png::IMAGE_DATA_CHUNK_SIZE];
+
+        loop {
+            let read = decoder.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            digest.write(&buffer[..read]);
+        }
+
+        Ok(digest.sum32())
+    }
+
+    /// Chunk types present in the file which are a near-match for the given type, e.g. differing
+    /// only by case or by a single character. Case-only matches are listed first.
+    fn similar_chunk_types(&self, chunk_type: &str) -> Vec<String> {
+        let mut case_matches = Vec::new();
+        let mut near_matches = Vec::new();
+
+        for candidate in self.chunks.iter().map(|c| c.chunk_type().to_string()) {
+            if candidate == chunk_type
+                || case_matches.contains(&candidate)
+                || near_matches.contains(&candidate)
+            {
+                continue;
+            }
+
+            if candidate.eq_ignore_ascii_case(chunk_type) {
+                case_matches.push(candidate);
+            } else if edit_distance(
+                &candidate.to_ascii_lowercase(),
+                &chunk_type.to_ascii_lowercase(),
+            ) <= 1
+            {
+                near_matches.push(candidate);
+            }
+        }
+
+        case_matches.append(&mut near_matches);
+        case_matches
+    }
+
+    /// Distinct private chunk types in the file, in order of first appearance
+    fn private_chunk_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = Vec::new();
+
+        for chunk in self.chunks.iter().filter(|c| !c.chunk_type().is_public()) {
+            let chunk_type = chunk.chunk_type().to_string();
+            if !types.contains(&chunk_type) {
+                types.push(chunk_type);
+            }
+        }
+
+        types
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let length = Png::STANDARD_HEADER.len()
+            + self
+                .chunks
+                .iter()
+                .map(|c| c.length() + Chunk::METADATA_BYTES)
+                .sum::<usize>()
+            + self.trailing_data.len();
+
+        let mut bytes = Vec::with_capacity(length);
+        bytes.extend_from_slice(self.header());
+
+        for chunk in &self.chunks {
+            chunk.write_to(&mut bytes);
+        }
+
+        bytes.extend_from_slice(&self.trailing_data);
+        bytes
+    }
+}
+
+/// Chunks are taken as they are, without checking the file is well-formed. See
+/// `Png::try_from_iter` for that
+impl FromIterator<Chunk> for Png {
+    fn from_iter<T: IntoIterator<Item = Chunk>>(iter: T) -> Self {
+        Png::from_chunks(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Chunk> for Png {
+    /// Chunks are inserted before IEND, if there is one, so the file stays valid
+    fn extend<T: IntoIterator<Item = Chunk>>(&mut self, iter: T) {
+        let end = self.end_index();
+        self.chunks.splice(end..end, iter);
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Png::parse_with_options(value, &ParseOptions::default())
+    }
+}
+
+/// Another file format found embedded in a PNG
+#[derive(Debug, PartialEq)]
+pub enum Embedded {
+    /// Found in the data of the chunk at the given index
+    Chunk(usize, FileType),
+
+    /// Found in the data after IEND
+    TrailingData(FileType),
+}
+
+/// Problems tolerated by lenient parsing, identifying the chunk by index
+#[derive(Debug, PartialEq)]
+pub enum ParseWarning {
+    /// The chunk had an all-zero placeholder CRC
+    PlaceholderCrc(usize),
+
+    /// The chunk ran past the end of the file, so only the available data was kept and its CRC
+    /// wasn't checked
+    Truncated(usize),
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseWarning::PlaceholderCrc(index) => {
+                write!(f, "Chunk {} has a placeholder CRC of zero", index)
+            }
+            ParseWarning::Truncated(index) => {
+                write!(
+                    f,
+                    "Chunk {} is truncated and only partially recovered",
+                    index
+                )
+            }
+        }
+    }
+}
+
+/// SplitMix64 step, used to generate noise images without pulling in a random number crate
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Whether a chunk failed to parse because it runs past the end of the input
+fn is_truncated(error: &Error) -> bool {
+    matches!(
+        error.downcast_ref::<ChunkError>(),
+        Some(ChunkError::Truncated { .. }) | Some(ChunkError::InputTooSmall)
+    )
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    /// The file doesn't start with the PNG signature. Holds the bytes found instead and the
+    /// format they look like, if it's recognised
+    InvalidHeader([u8; 8], Option<FileType>),
+    TooSmall,
+
+    /// File size exceeds the configured maximum
+    TooLarge(usize, usize),
+
+    /// File contains more chunks than the configured maximum
+    TooManyChunks(usize),
+
+    /// The requested chunk type wasn't found, along with any similar types which were and the
+    /// private types present, which are the likeliest to hold a message
+    UnknownChunkType(String, Vec<String>, Vec<String>),
+
+    /// A chunk required by the spec is missing
+    MissingChunk(&'static str),
+
+    /// A chunk which has to be in the given position, first or last, isn't
+    MisplacedChunk(&'static str, &'static str),
+
+    /// An animation operation was requested on a PNG without an acTL chunk
+    NotAnimated,
+
+    /// The animation doesn't have a frame with the given index
+    UnknownFrame(usize),
+
+    /// Image data can't be split into chunks of zero bytes
+    InvalidChunkSize,
+
+    /// A chunk can't be placed at the given index, which must be between 1 and the second value
+    InvalidPosition(usize, usize),
+}
+
+impl std::error::Error for PngError {}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::InvalidHeader(actual, file_type) => {
+                write!(f, "Invalid header. Mismatched signature bytes:")?;
+
+                for (offset, (actual, expected)) in
+                    actual.iter().zip(Png::STANDARD_HEADER.iter()).enumerate()
+                {
+                    if actual != expected {
+                        write!(
+                            f,
+                            " offset {} is 0x{:02X} (expected 0x{:02X})",
+                            offset, actual, expected
+                        )?;
+                    }
+                }
+
+                match file_type {
+                    Some(file_type) => {
+                        write!(f, ". The file appears to be {} rather than PNG", file_type)
+                    }
+                    None => Ok(()),
+                }
+            }
+            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
+            PngError::TooLarge(size, max) => write!(
+                f,
+                "The given source is {} bytes but at most {} are allowed",
+                size, max
+            ),
+            PngError::TooManyChunks(max) => {
+                write!(f, "The given source contains more than {} chunks", max)
+            }
+            PngError::MissingChunk(chunk_type) => write!(f, "Missing {} chunk", chunk_type),
+            PngError::MisplacedChunk(chunk_type, position) => {
+                write!(f, "The {} chunk must be {}", chunk_type, position)
+            }
+            PngError::NotAnimated => write!(f, "The PNG isn't animated"),
+            PngError::UnknownFrame(index) => write!(f, "The animation has no frame {}", index),
+            PngError::InvalidChunkSize => write!(f, "Image data chunk size must be non-zero"),
+            PngError::InvalidPosition(index, end) => write!(
+                f,
+                "Chunks can only be placed at positions 1 to {}, between IHDR and IEND, not {}",
+                end, index
+            ),
+            PngError::UnknownChunkType(chunk_type, suggestions, private) => {
+                write!(f, "Unknown chunk type '{}'", chunk_type)?;
+
+                let quote = |types: &[String]| -> Vec<String> {
+                    types.iter().map(|s| format!("'{}'", s)).collect()
+                };
+
+                if suggestions.is_empty() {
+                    if !private.is_empty() {
+                        write!(
+                            f,
+                            ". Private chunks in this file are {}",
+                            quote(private).join(", ")
+                        )?;
+                    }
+                    return Ok(());
+                }
+
+                let quoted = quote(suggestions);
+                write!(f, ". Did you mean {}?", quoted.join(" or "))?;
+
+                if suggestions
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(chunk_type))
+                {
+                    write!(f, " Chunk types are case-sensitive")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_data::SuggestedPaletteEntry;
+    use crate::chunk_type::ChunkType;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
+    }
+
+    fn testing_png() -> Png {
+        let chunks = testing_chunks();
+        Png::from_chunks(chunks)
+    }
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+        let data: Vec<u8> = data.bytes().collect();
+
+        Ok(Chunk::new(chunk_type, data))
+    }
+
+    #[test]
+    fn test_from_chunks() {
+        let chunks = testing_chunks();
+        let png = Png::from_chunks(chunks);
+
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_valid_from_bytes() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .chain(chunk_bytes.iter())
+            .copied()
+            .collect();
+
+        let png = Png::try_from(bytes.as_ref());
+
+        assert!(png.is_ok());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let bytes: Vec<u8> = [13, 80, 78, 71, 13, 10, 26, 10]
+            .iter()
+            .chain(chunk_bytes.iter())
+            .copied()
+            .collect();
+
+        let png = Png::try_from(bytes.as_ref());
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_invalid_header_reports_mismatched_bytes() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[0] = 13;
+        bytes[3] = 72;
+
+        let error = Png::try_from(bytes.as_ref()).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid header. Mismatched signature bytes: \
+            offset 0 is 0x0D (expected 0x89) offset 3 is 0x48 (expected 0x47)"
+        );
+    }
+
+    #[test]
+    fn test_invalid_header_detects_other_formats() {
+        let bytes = [0xFF, 0xD8, 0xFF, 0xE0, 0, 16, 74, 70, 73, 70, 0, 1];
+
+        let error = Png::try_from(&bytes[..]).err().unwrap();
+
+        assert!(error
+            .to_string()
+            .ends_with("The file appears to be JPEG rather than PNG"));
+    }
+
+    #[test]
+    fn test_invalid_chunk() {
+        let mut chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        #[rustfmt::skip]
+        let mut bad_chunk = vec![
+            0, 0, 0, 5,         // length
+            32, 117, 83, 116,   // Chunk Type (bad)
+            65, 64, 65, 66, 67, // Data
+            1, 2, 3, 4, 5       // CRC (bad)
+        ];
+
+        chunk_bytes.append(&mut bad_chunk);
+
+        let png = Png::try_from(chunk_bytes.as_ref());
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_file_exceeding_max_size() {
+        let options = ParseOptions {
+            max_file_size: PNG_FILE.len() - 1,
+            ..ParseOptions::default()
+        };
+
+        let png = Png::parse_with_options(&PNG_FILE[..], &options);
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_file_exceeding_max_chunks() {
+        let options = ParseOptions {
+            max_chunks: 2,
+            ..ParseOptions::default()
+        };
+
+        let png = Png::parse_with_options(&testing_png().as_bytes(), &options);
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_file_within_limits() {
+        let options = ParseOptions {
+            max_chunks: 3,
+            ..ParseOptions::default()
+        };
+
+        let png = Png::parse_with_options(&testing_png().as_bytes(), &options);
+
+        assert!(png.is_ok());
+    }
+
+    #[test]
+    fn test_placeholder_crc_is_reported() {
+        let mut bytes = testing_png().as_bytes();
+        let first_crc = Png::STANDARD_HEADER.len() + 20 + Chunk::METADATA_BYTES - Chunk::CRC_BYTES;
+        bytes[first_crc..first_crc + Chunk::CRC_BYTES].copy_from_slice(&[0, 0, 0, 0]);
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let options = ParseOptions {
+            lenient_crc: true,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse_with_options(&bytes, &options).unwrap();
+
+        assert_eq!(png.warnings(), &[ParseWarning::PlaceholderCrc(0)]);
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+    }
+
+    #[test]
+    fn test_truncated_chunk_is_salvaged() {
+        let bytes = testing_png().as_bytes();
+        let truncated = &bytes[..bytes.len() - 10];
+
+        assert!(Png::try_from(truncated).is_err());
+
+        let options = ParseOptions {
+            lenient_truncation: true,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse_with_options(truncated, &options).unwrap();
+
+        let last = png.chunks().last().unwrap();
+        assert_eq!(png.chunks().len(), testing_chunks().len());
+        assert_eq!(png.warnings(), &[ParseWarning::Truncated(2)]);
+        assert_eq!(last.data(), &testing_chunks()[2].data()[..last.length()]);
+    }
+
+    #[test]
+    fn test_truncated_chunk_type_is_not_salvaged() {
+        let bytes = testing_png().as_bytes();
+        let options = ParseOptions {
+            lenient_truncation: true,
+            ..ParseOptions::default()
+        };
+
+        assert!(Png::parse_with_options(&bytes[..bytes.len() - 24], &options).is_err());
+    }
+
+    #[test]
+    fn test_trailing_data_is_kept() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"PK\x03\x04 not really a zip");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(png.trailing_data(), b"PK\x03\x04 not really a zip");
+        assert_eq!(png.trailing_data_type(), Some(FileType::Zip));
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_strip_trailing_data() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"secret");
+
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(png.strip_trailing_data(), b"secret");
+        assert!(png.trailing_data().is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_no_trailing_data() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.trailing_data().is_empty());
+        assert_eq!(png.trailing_data_type(), None);
+    }
+
+    #[test]
+    fn test_embedded_pdf_in_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "junk %PDF-1.4 junk").unwrap());
+
+        assert_eq!(
+            png.embedded_formats(),
+            vec![Embedded::Chunk(3, FileType::Pdf)]
+        );
+    }
+
+    #[test]
+    fn test_embedded_zip_in_trailing_data() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"PK\x03\x04 ... PK\x05\x06");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(
+            png.embedded_formats(),
+            vec![Embedded::TrailingData(FileType::Zip)]
+        );
+    }
+
+    #[test]
+    fn test_no_embedded_formats() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.embedded_formats().is_empty());
+    }
+
+    #[test]
+    fn test_png_is_not_animated() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.animation().unwrap(), None);
+    }
+
+    fn testing_animated_png() -> Png {
+        #[rustfmt::skip]
+        let frame_control = |sequence: u8, width: u8| vec![
+            0, 0, 0, sequence,
+            0, 0, 0, width,
+            0, 0, 0, 10,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 1, 0, 10,
+            0, 0,
+        ];
+
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 20, 0, 0, 0, 10, 8, 3, 0, 0, 0]),
+            chunk("acTL", vec![0, 0, 0, 2, 0, 0, 0, 0]),
+            chunk("PLTE", vec![0, 0, 0, 255, 255, 255]),
+            chunk("fcTL", frame_control(0, 20)),
+            chunk("IDAT", vec![1, 2, 3]),
+            chunk("fcTL", frame_control(1, 5)),
+            chunk("fdAT", vec![0, 0, 0, 2, 4, 5, 6]),
+            chunk("fdAT", vec![0, 0, 0, 3, 7, 8]),
+            chunk("IEND", Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut png = testing_animated_png();
+        assert_eq!(png.histogram().unwrap(), None);
+
+        png.append_chunk(
+            Histogram {
+                frequencies: vec![3, 4],
+            }
+            .to_chunk(),
+        );
+        assert_eq!(png.histogram().unwrap().unwrap().frequencies, vec![3, 4]);
+
+        png.remove_chunk("hIST").unwrap();
+        png.append_chunk(
+            Histogram {
+                frequencies: vec![3, 4, 5],
+            }
+            .to_chunk(),
+        );
+        assert!(png.histogram().is_err());
+    }
+
+    #[test]
+    fn test_suggested_palettes() {
+        let mut png = testing_animated_png();
+        assert!(png.suggested_palettes().unwrap().is_empty());
+
+        let palette = |name: &str| SuggestedPalette {
+            name: String::from(name),
+            sample_depth: 8,
+            entries: vec![SuggestedPaletteEntry {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+                frequency: 1,
+            }],
+        };
+        png.append_chunk(palette("web").to_chunk().unwrap());
+        png.append_chunk(palette("print").to_chunk().unwrap());
+
+        assert_eq!(
+            png.suggested_palettes().unwrap(),
+            vec![palette("web"), palette("print")]
+        );
+    }
+
+    #[test]
+    fn test_histogram_without_palette() {
+        let mut png = testing_png();
+        png.append_chunk(
+            Histogram {
+                frequencies: vec![3],
+            }
+            .to_chunk(),
+        );
+        assert!(png.histogram().is_err());
+    }
+
+    #[test]
+    fn test_background_and_transparency() {
+        let mut png = testing_animated_png();
+        assert_eq!(png.background().unwrap(), None);
+        assert_eq!(png.transparency().unwrap(), None);
+
+        png.append_chunk(Background::PaletteIndex(1).to_chunk());
+        png.append_chunk(Transparency::PaletteAlpha(vec![0]).to_chunk());
+
+        assert_eq!(png.background().unwrap(), Some(Background::PaletteIndex(1)));
+        assert_eq!(
+            png.transparency().unwrap(),
+            Some(Transparency::PaletteAlpha(vec![0]))
+        );
+    }
+
+    #[test]
+    fn test_background_outside_palette() {
+        let mut png = testing_animated_png();
+        png.append_chunk(Background::PaletteIndex(2).to_chunk());
+        assert!(png.background().is_err());
+    }
+
+    #[test]
+    fn test_background_without_header() {
+        let mut png = testing_png();
+        png.append_chunk(Background::Gray(0).to_chunk());
+        assert!(png.background().is_err());
+    }
+
+    #[test]
+    fn test_icc_profile() {
+        let mut png = testing_animated_png();
+        assert_eq!(png.icc_profile().unwrap(), None);
+
+        let mut profile = IccProfile {
+            name: String::from("sRGB"),
+            profile: b"profile".to_vec(),
+        };
+        png.set_icc_profile(&profile).unwrap();
+        profile.profile = b"replacement profile".to_vec();
+        png.set_icc_profile(&profile).unwrap();
+
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "iCCP");
+        assert_eq!(png.chunks().len(), 10);
+        assert_eq!(png.icc_profile().unwrap(), Some(profile));
+
+        png.remove_chunk(IccProfile::CHUNK_TYPE).unwrap();
+        assert_eq!(png.icc_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn test_icc_profile_uses_parse_options() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let profile = IccProfile {
+            name: String::from("Display P3"),
+            profile: vec![0; 4096],
+        };
+        png.set_icc_profile(&profile).unwrap();
+        let bytes = png.as_bytes();
+
+        let options = ParseOptions {
+            max_inflated_length: 1024,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse_with_options(&bytes, &options).unwrap();
+        assert!(png.icc_profile().is_err());
+
+        let options = ParseOptions {
+            max_inflated_length: usize::MAX,
+            max_inflate_ratio: usize::MAX,
+            ..ParseOptions::default()
+        };
+        let png = Png::parse_with_options(&bytes, &options).unwrap();
+        assert_eq!(png.icc_profile().unwrap(), Some(profile));
+    }
+
+    #[test]
+    fn test_icc_profile_replaces_srgb() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.chunk_by_type("sRGB").is_some());
+
+        let profile = IccProfile {
+            name: String::from("Display P3"),
+            profile: b"profile".to_vec(),
+        };
+        png.set_icc_profile(&profile).unwrap();
+
+        assert!(png.chunk_by_type("sRGB").is_none());
+        assert!(png.chunks()[1].chunk_type().matches("iCCP"));
+    }
+
+    #[test]
+    fn test_xmp() {
+        let mut png = testing_png();
+        assert_eq!(png.xmp().unwrap(), None);
+
+        let title = InternationalText {
+            keyword: String::from("Title"),
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: String::from("Dice"),
+        };
+        png.append_chunk(title.to_chunk().unwrap());
+
+        png.set_xmp("<x:xmpmeta/>").unwrap();
+        png.set_xmp("<x:xmpmeta>replaced</x:xmpmeta>").unwrap();
+
+        assert_eq!(png.chunks().len(), 5);
+        assert_eq!(
+            png.xmp().unwrap(),
+            Some(String::from("<x:xmpmeta>replaced</x:xmpmeta>"))
+        );
     }
 
-    fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+    #[test]
+    fn test_xmp_ignores_invalid_text_chunks() {
+        let mut png = testing_png();
+        let invalid_utf8 = b"Title\0\0\0\0\0\xff\xfe".to_vec();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str(InternationalText::CHUNK_TYPE).unwrap(),
+            invalid_utf8,
+        ));
+        png.set_xmp("<x:xmpmeta/>").unwrap();
+
+        assert_eq!(png.xmp().unwrap(), Some(String::from("<x:xmpmeta/>")));
     }
 
-    fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let index = self
-            .chunks
+    #[test]
+    fn test_extract_frame() {
+        let png = testing_animated_png();
+
+        let frame = png.extract_frame(1).unwrap();
+        let types: Vec<String> = frame
+            .chunks()
             .iter()
-            .position(|c| (*c).chunk_type().to_string() == chunk_type)
-            .ok_or(PngError::UnknownChunkType)?;
-        let removed = self.chunks.remove(index);
-        Ok(removed)
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(types, vec!["IHDR", "PLTE", "IDAT", "IDAT", "IEND"]);
+
+        let header = ImageHeader::try_from(&frame.chunks()[0]).unwrap();
+        assert_eq!((header.width, header.height), (5, 10));
+        assert_eq!(frame.chunks()[2].data(), &[4, 5, 6]);
+        assert_eq!(frame.chunks()[3].data(), &[7, 8]);
+        assert!(Png::try_from(frame.as_bytes().as_ref()).is_ok());
     }
 
-    fn header(&self) -> &[u8; 8] {
-        &Png::STANDARD_HEADER
+    #[test]
+    fn test_extract_first_frame_uses_idat() {
+        let png = testing_animated_png();
+        let frame = png.extract_frame(0).unwrap();
+        assert_eq!(frame.chunks()[2].data(), &[1, 2, 3]);
     }
 
-    fn chunks(&self) -> &[Chunk] {
-        &self.chunks
+    #[test]
+    fn test_extract_unknown_frame() {
+        let png = testing_animated_png();
+        assert!(png.extract_frame(2).is_err());
     }
 
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        self.chunks
+    #[test]
+    fn test_extract_frame_not_animated() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.extract_frame(0).is_err());
+    }
+
+    #[test]
+    fn test_strip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("tEXt", "Software\0pngme").unwrap());
+
+        let removed = png.strip(StripPreset::Privacy);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(png.chunks().len(), 7);
+
+        let removed = png.strip(StripPreset::Minimal);
+        let types: Vec<String> = png
+            .chunks()
             .iter()
-            .find(|&c| c.chunk_type().to_string() == chunk_type)
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        // RuSt is a private but critical chunk, so it has to stay
+        assert_eq!(removed.len(), 3);
+        assert_eq!(types, vec!["IHDR", "IDAT", "RuSt", "IEND"]);
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        let header: Vec<u8> = self.header().iter().copied().collect();
-        let body: Vec<u8> = self
-            .chunks
+    #[test]
+    fn test_anonymize() {
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+            chunk("tEXt", b"Software\0pngme".to_vec()),
+            chunk("PLTE", vec![0, 0, 0]),
+            chunk("tRNS", vec![0]),
+            chunk("IDAT", vec![1; 5000]),
+            chunk("IDAT", vec![2; 5000]),
+            chunk("tIME", vec![7, 228, 1, 1, 0, 0, 0]),
+            chunk("IEND", Vec::new()),
+        ]);
+
+        png.anonymize().unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
             .iter()
-            .flat_map(|c| c.as_bytes().into_iter())
-            .collect::<Vec<_>>();
+            .map(|c| c.chunk_type().to_string())
+            .collect();
 
-        header.into_iter().chain(body.into_iter()).collect()
+        assert_eq!(types, vec!["IHDR", "PLTE", "tRNS", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(png.chunks()[3].length(), Png::IMAGE_DATA_CHUNK_SIZE);
+        assert_eq!(png.chunks()[4].length(), 10000 - Png::IMAGE_DATA_CHUNK_SIZE);
+        assert_eq!(png.chunks()[4].data().last(), Some(&2));
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    #[test]
+    fn test_anonymize_keeps_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image_data = png.chunk_by_type("IDAT").unwrap().clone();
 
-    fn try_from(value: &[u8]) -> Result<Self> {
-        if value.len() < Png::STANDARD_HEADER.len() {
-            return Err(Box::from(PngError::TooSmall));
-        }
+        png.anonymize().unwrap();
 
-        // TODO: there's got to be a nicer way than advancing a pointer and reslicing each time
-        let mut index = 0;
+        assert_eq!(png.chunk_by_type("IDAT"), Some(&image_data));
+    }
 
-        // validate header
-        let header = &value[index..index + Png::STANDARD_HEADER.len()];
-        index += Png::STANDARD_HEADER.len();
+    #[test]
+    fn test_rechunk_to_zero_size() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.rechunk_image_data(0).is_err());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
 
-        if Png::STANDARD_HEADER != header {
-            return Err(Box::from(PngError::InvalidHeader));
-        }
+    #[test]
+    fn test_list_chunks() {
+        let png = testing_png();
+        let chunks = png.chunks();
+        assert_eq!(chunks.len(), 3);
+    }
 
-        // parse one chunk at a time
-        let mut chunks = Vec::new();
+    #[test]
+    fn test_chunk_by_type() {
+        let png = testing_png();
+        let chunk = png.chunk_by_type("FrSt").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
+    }
 
-        while index < value.len() {
-            let bytes = &value[index..];
-            let chunk = Chunk::try_from(bytes)?;
-            index += chunk.length() + Chunk::METADATA_BYTES;
+    #[test]
+    fn test_append_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let chunk = png.chunk_by_type("TeSt").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
+        assert_eq!(&chunk.data_as_string().unwrap(), "Message");
+    }
 
-            chunks.push(chunk);
-        }
+    #[test]
+    fn test_append_chunk_before_end() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
 
-        Ok(Png { chunks })
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .rev()
+            .take(2)
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, vec!["IEND", "TeSt"]);
     }
-}
 
-#[derive(Debug)]
-pub enum PngError {
-    InvalidHeader,
-    TooSmall,
-    UnknownChunkType,
-}
+    #[test]
+    fn test_metadata_survives_round_trip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut annotations = Annotations::new();
+        annotations.insert("source", "camera-7").unwrap();
+        png.set_annotations(&annotations);
+        png.set_xmp("<x:xmpmeta/>").unwrap();
 
-impl std::error::Error for PngError {}
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
 
-impl Display for PngError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            PngError::InvalidHeader => write!(f, "Invalid header"),
-            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
-            PngError::UnknownChunkType => write!(f, "Unknown chunk type"),
-        }
+        assert!(png.trailing_data().is_empty());
+        assert_eq!(png.annotations().unwrap(), annotations);
+        assert_eq!(png.xmp().unwrap(), Some(String::from("<x:xmpmeta/>")));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::chunk::Chunk;
-    use crate::chunk_type::ChunkType;
-    use std::convert::TryFrom;
-    use std::str::FromStr;
+    #[test]
+    fn test_remove_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.remove_chunk("TeSt").unwrap();
+        let chunk = png.chunk_by_type("TeSt");
+        assert!(chunk.is_none());
+    }
 
-    fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::with_capacity(3);
+    #[test]
+    fn test_remove_unknown_chunk_suggests_case_match() {
+        let mut png = testing_png();
+        let error = png.remove_chunk("frst").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown chunk type 'frst'. Did you mean 'FrSt'? Chunk types are case-sensitive"
+        );
+    }
 
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
+    #[test]
+    fn test_remove_unknown_chunk_suggests_near_match() {
+        let mut png = testing_png();
+        let error = png.remove_chunk("LASx").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown chunk type 'LASx'. Did you mean 'LASt'?"
+        );
+    }
+
+    #[test]
+    fn test_find_chunk() {
+        let png = testing_png();
+        assert_eq!(
+            &png.find_chunk("miDl").unwrap().data_as_string().unwrap(),
+            "I am another chunk"
+        );
 
-        chunks
+        let error = png.find_chunk("midl").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown chunk type 'midl'. Did you mean 'miDl'? Chunk types are case-sensitive"
+        );
     }
 
-    fn testing_png() -> Png {
-        let chunks = testing_chunks();
-        Png::from_chunks(chunks)
+    #[test]
+    fn test_remove_unknown_chunk_lists_private_chunks() {
+        let mut png = testing_png();
+        let error = png.remove_chunk("ZzZz").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown chunk type 'ZzZz'. Private chunks in this file are 'FrSt', 'miDl'"
+        );
     }
 
-    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
-        let chunk_type = ChunkType::from_str(chunk_type)?;
-        let data: Vec<u8> = data.bytes().collect();
+    #[test]
+    fn test_remove_unknown_chunk_without_suggestions() {
+        let mut png = Png::solid_color(1, 1, [0, 0, 0]).unwrap();
+        let error = png.remove_chunk("ZzZz").unwrap_err();
+        assert_eq!(error.to_string(), "Unknown chunk type 'ZzZz'");
+    }
 
-        Ok(Chunk::new(chunk_type, data))
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let replacement = chunk_from_strings("miDl", "I replaced the middle").unwrap();
+
+        let replaced = png.replace_chunk("miDl", replacement.clone()).unwrap();
+
+        assert_eq!(&replaced.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(png.chunks()[1], replacement);
+        assert!(png.replace_chunk("ZzZz", replaced).is_err());
     }
 
     #[test]
-    fn test_from_chunks() {
-        let chunks = testing_chunks();
-        let png = Png::from_chunks(chunks);
+    fn test_chunk_mut() {
+        let mut png = testing_png();
+        *png.chunk_mut("LASt").unwrap() = chunk_from_strings("LASt", "Edited").unwrap();
 
-        assert_eq!(png.chunks().len(), 3);
+        let chunk = png.chunk_by_type("LASt").unwrap();
+        assert_eq!(&chunk.data_as_string().unwrap(), "Edited");
+        assert!(png.chunk_mut("ZzZz").is_none());
     }
 
     #[test]
-    fn test_valid_from_bytes() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
-            .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
+    fn test_retain() {
+        let mut png = testing_png();
+        png.retain(|c| c.chunk_type().is_critical());
 
-        let bytes: Vec<u8> = Png::STANDARD_HEADER
+        let chunk_types: Vec<String> = png
+            .chunks()
             .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
+            .map(|c| c.chunk_type().to_string())
             .collect();
+        assert_eq!(chunk_types, vec!["FrSt", "LASt"]);
+    }
 
-        let png = Png::try_from(bytes.as_ref());
+    #[test]
+    fn test_swap() {
+        let mut png = testing_png();
+        png.swap(0, 2);
 
-        assert!(png.is_ok());
+        assert!(png.chunks()[0].chunk_type().matches("LASt"));
+        assert!(png.chunks()[2].chunk_type().matches("FrSt"));
     }
 
     #[test]
-    fn test_invalid_header() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
-            .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
+    fn test_insert_chunk() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let count = png.chunks().len();
+        let chunk = chunk_from_strings("ruSt", "inserted").unwrap();
 
-        let bytes: Vec<u8> = [13, 80, 78, 71, 13, 10, 26, 10]
-            .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
-            .collect();
+        png.insert_chunk(1, chunk.clone()).unwrap();
+        assert_eq!(png.chunks()[1], chunk);
 
-        let png = Png::try_from(bytes.as_ref());
+        png.insert_chunk(count, chunk.clone()).unwrap();
+        assert_eq!(png.chunks()[count], chunk);
+        assert!(png.chunks()[count + 1].chunk_type().matches("IEND"));
 
-        assert!(png.is_err());
+        assert!(png.insert_chunk(0, chunk.clone()).is_err());
+        assert!(png.insert_chunk(count + 2, chunk).is_err());
     }
 
     #[test]
-    fn test_invalid_chunk() {
-        let mut chunk_bytes: Vec<u8> = testing_chunks()
+    fn test_from_iterator() {
+        let png: Png = testing_chunks()
             .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
+            .filter(|c| c.chunk_type().is_critical())
             .collect();
 
-        #[rustfmt::skip]
-        let mut bad_chunk = vec![
-            0, 0, 0, 5,         // length
-            32, 117, 83, 116,   // Chunk Type (bad)
-            65, 64, 65, 66, 67, // Data
-            1, 2, 3, 4, 5       // CRC (bad)
-        ];
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunks()[1].chunk_type().matches("LASt"));
+    }
 
-        chunk_bytes.append(&mut bad_chunk);
+    #[test]
+    fn test_try_from_iter() {
+        let chunks = Png::try_from(&PNG_FILE[..]).unwrap().chunks().to_vec();
+        let text = chunk_from_strings("teSt", "kept").unwrap();
 
-        let png = Png::try_from(chunk_bytes.as_ref());
+        let png = Png::try_from_iter(chunks.iter().cloned().chain(Some(text.clone()))).err();
+        assert_eq!(png.unwrap().to_string(), "The IEND chunk must be last");
 
-        assert!(png.is_err());
-    }
+        let png = Png::try_from_iter(chunks.iter().skip(1).cloned()).err();
+        assert_eq!(png.unwrap().to_string(), "The IHDR chunk must be first");
 
-    #[test]
-    fn test_list_chunks() {
-        let png = testing_png();
-        let chunks = png.chunks();
-        assert_eq!(chunks.len(), 3);
+        assert!(Png::try_from_iter(Vec::new()).is_err());
+
+        let mut png = Png::try_from_iter(chunks.iter().cloned()).unwrap();
+        png.extend(Some(text));
+        assert!(Png::try_from_iter(png.chunks().to_vec()).is_ok());
     }
 
     #[test]
-    fn test_chunk_by_type() {
-        let png = testing_png();
-        let chunk = png.chunk_by_type("FrSt").unwrap();
-        assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
-        assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
+    fn test_extend_inserts_before_end() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let count = png.chunks().len();
+        png.extend(vec![
+            chunk_from_strings("teSt", "first").unwrap(),
+            chunk_from_strings("teSt", "second").unwrap(),
+        ]);
+
+        assert_eq!(png.chunks().len(), count + 2);
+        assert_eq!(&png.chunks()[count - 1].data_as_string().unwrap(), "first");
+        assert_eq!(&png.chunks()[count].data_as_string().unwrap(), "second");
+        assert!(png.chunks()[count + 1].chunk_type().matches("IEND"));
     }
 
     #[test]
-    fn test_append_chunk() {
+    fn test_extend_without_end() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        let chunk = png.chunk_by_type("TeSt").unwrap();
-        assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
-        assert_eq!(&chunk.data_as_string().unwrap(), "Message");
+        png.extend(std::iter::once(chunk_from_strings("teSt", "last").unwrap()));
+
+        assert!(png.chunks().last().unwrap().chunk_type().matches("teSt"));
     }
 
     #[test]
-    fn test_remove_chunk() {
+    fn test_annotations() {
         let mut png = testing_png();
+        assert!(png.annotations().unwrap().is_empty());
+
+        let mut annotations = Annotations::new();
+        annotations.insert("source", "camera-7").unwrap();
+        png.set_annotations(&annotations);
         png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        png.remove_chunk("TeSt").unwrap();
-        let chunk = png.chunk_by_type("TeSt");
-        assert!(chunk.is_none());
+
+        annotations.insert("operator", "arodger").unwrap();
+        png.set_annotations(&annotations);
+
+        assert_eq!(png.chunks().len(), 5);
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "anOt");
+        assert_eq!(png.annotations().unwrap(), annotations);
     }
 
     #[test]
@@ -245,10 +1852,159 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_high_entropy_chunks() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.high_entropy_chunks().count(), 0);
+
+        let random = Chunk::new(ChunkType::from_str("ruSt").unwrap(), (0..=255).collect());
+        png.append_chunk(chunk_from_strings("teSt", "plain old text").unwrap());
+        png.append_chunk(random.clone());
+
+        let found: Vec<(usize, &Chunk)> = png.high_entropy_chunks().collect();
+        assert_eq!(found, vec![(png.chunks().len() - 2, &random)]);
+    }
+
+    #[test]
+    fn test_short_high_entropy_chunk() {
+        // xorshift, so the "encrypted" bytes are the same on every run
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let random: Vec<u8> = (0..64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(
+            chunk_from_strings("teSt", "This is where your secret message will be!").unwrap(),
+        );
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), random));
+
+        let found: Vec<String> = png
+            .high_entropy_chunks()
+            .map(|(_, c)| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(found, vec!["ruSt"]);
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let png = Png::solid_color(300, 200, [255, 128, 0]).unwrap();
+        let (info, pixels) = decode_pixels(&png.as_bytes());
+
+        assert_eq!((info.width, info.height), (300, 200));
+        assert_eq!(info.color_type, ::png::ColorType::Rgb);
+        assert!(pixels.chunks(3).all(|p| p == [255, 128, 0]));
+    }
+
+    #[test]
+    fn test_solid_color_needs_dimensions() {
+        assert!(Png::solid_color(0, 10, [0, 0, 0]).is_err());
+        assert!(Png::solid_color(u32::MAX, 10, [0, 0, 0]).is_err());
+        assert!(Png::noise(10, Png::MAX_GENERATED_DIMENSION + 1, 0).is_err());
+    }
+
+    #[test]
+    fn test_noise() {
+        let png = Png::noise(101, 50, 42).unwrap();
+        let (info, pixels) = decode_pixels(&png.as_bytes());
+
+        assert_eq!((info.width, info.height), (101, 50));
+        assert_eq!(pixels.len(), 101 * 50 * 3);
+
+        let pixels = Chunk::new(ChunkType::from_str("ruSt").unwrap(), pixels);
+        assert!(pixels.entropy() > 7.9);
+
+        assert_eq!(Png::noise(101, 50, 42).unwrap().as_bytes(), png.as_bytes());
+        assert_ne!(Png::noise(101, 50, 43).unwrap().as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_gradient() {
+        let png = Png::gradient(256, 128).unwrap();
+        let (info, pixels) = decode_pixels(&png.as_bytes());
+        let pixel = |x: usize, y: usize| &pixels[(y * 256 + x) * 3..][..3];
+
+        assert_eq!((info.width, info.height), (256, 128));
+        assert_eq!(pixel(0, 0), [0, 0, 0]);
+        assert_eq!(pixel(255, 0), [255, 0, 0]);
+        assert_eq!(pixel(0, 127), [0, 255, 0]);
+        assert_eq!(pixel(255, 127), [255, 255, 255]);
+        assert!(Png::gradient(1, 1).is_ok());
+    }
+
+    #[test]
+    fn test_image_data_checksum() {
+        let original = Png::try_from(&PNG_FILE[..]).unwrap();
+        let checksum = original.image_data_checksum().unwrap();
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.rechunk_image_data(100).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden message").unwrap());
+        assert_eq!(png.image_data_checksum().unwrap(), checksum);
+
+        png.remove_chunk(Png::IMAGE_DATA_CHUNK_TYPE).unwrap();
+        assert!(png.image_data_checksum().is_err());
+    }
+
+    /// Decode pixels with the independent `png` crate, so that mutations can be checked for
+    /// render compatibility rather than just round-tripping through our own parser
+    fn decode_pixels(bytes: &[u8]) -> (::png::OutputInfo, Vec<u8>) {
+        let mut reader = ::png::Decoder::new(bytes).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+        (info, pixels)
+    }
+
+    fn assert_renders_identically(png: &Png) {
+        let (expected_info, expected_pixels) = decode_pixels(&PNG_FILE);
+        let (actual_info, actual_pixels) = decode_pixels(&png.as_bytes());
+
+        assert_eq!(actual_info.width, expected_info.width);
+        assert_eq!(actual_info.height, expected_info.height);
+        assert_eq!(actual_info.color_type, expected_info.color_type);
+        assert_eq!(actual_pixels, expected_pixels);
+    }
+
+    #[test]
+    fn test_append_chunk_renders_identically() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden message").unwrap());
+        assert_renders_identically(&png);
+    }
+
+    #[test]
+    fn test_annotations_render_identically() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut annotations = Annotations::new();
+        annotations.insert("source", "camera-7").unwrap();
+        png.set_annotations(&annotations);
+        assert_renders_identically(&png);
+    }
+
+    #[test]
+    fn test_strip_renders_identically() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.strip(StripPreset::Web);
+        assert_renders_identically(&png);
+    }
+
+    #[test]
+    fn test_rechunk_renders_identically() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.rechunk_image_data(1000).unwrap();
+        assert_renders_identically(&png);
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
@@ -0,0 +1,160 @@
+use crate::chunk::Chunk;
+use crate::Error;
+use std::{fmt::Display, str::FromStr};
+
+/// Named sets of chunks to remove from a PNG
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StripPreset {
+    /// Remove identifying metadata (location, timestamps, camera and software details) but keep
+    /// everything else
+    Privacy,
+
+    /// Keep only what browsers need to display the image correctly, including colour management
+    Web,
+
+    /// Keep only what's needed to decode the image at all
+    Minimal,
+}
+
+impl StripPreset {
+    /// Chunks, besides the critical ones, which are needed to decode the image correctly
+    const RENDERING_CHUNK_TYPES: [&'static str; 4] = ["tRNS", "acTL", "fcTL", "fdAT"];
+
+    /// Colour management and physical size chunks which browsers honour
+    const WEB_CHUNK_TYPES: [&'static str; 6] = ["gAMA", "cHRM", "sRGB", "iCCP", "cICP", "pHYs"];
+
+    /// Chunks which only hold identifying information
+    const IDENTIFYING_CHUNK_TYPES: [&'static str; 2] = ["eXIf", "tIME"];
+
+    /// Text chunk keywords which identify who or what produced the image, and when
+    const IDENTIFYING_KEYWORDS: [&'static str; 6] = [
+        "Author",
+        "Creation Time",
+        "Software",
+        "Source",
+        "Comment",
+        "XML:com.adobe.xmp",
+    ];
+
+    const TEXT_CHUNK_TYPES: [&'static str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+    /// Whether the preset removes the given chunk
+    pub fn removes(self, chunk: &Chunk) -> bool {
+        if chunk.chunk_type().is_critical() {
+            return false;
+        }
+
+        let is_one_of = |types: &[&str]| types.iter().any(|&t| chunk.chunk_type().matches(t));
+
+        match self {
+            StripPreset::Privacy => {
+                is_one_of(&StripPreset::IDENTIFYING_CHUNK_TYPES)
+                    || (is_one_of(&StripPreset::TEXT_CHUNK_TYPES)
+                        && StripPreset::IDENTIFYING_KEYWORDS
+                            .iter()
+                            .any(|k| k.as_bytes() == keyword(chunk)))
+            }
+            StripPreset::Web => {
+                !is_one_of(&StripPreset::RENDERING_CHUNK_TYPES)
+                    && !is_one_of(&StripPreset::WEB_CHUNK_TYPES)
+            }
+            StripPreset::Minimal => !is_one_of(&StripPreset::RENDERING_CHUNK_TYPES),
+        }
+    }
+}
+
+/// Keyword of a text chunk, which is always the data up to the first NUL. The identifying
+/// keywords are all ASCII, so comparing the raw Latin-1 bytes is enough
+fn keyword(chunk: &Chunk) -> &[u8] {
+    let data = chunk.data();
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    &data[..end]
+}
+
+impl FromStr for StripPreset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "privacy" => Ok(StripPreset::Privacy),
+            "web" => Ok(StripPreset::Web),
+            "minimal" => Ok(StripPreset::Minimal),
+            _ => Err(Box::new(StripPresetError(s.to_string()))),
+        }
+    }
+}
+
+/// Unknown strip preset name
+#[derive(Debug)]
+pub struct StripPresetError(String);
+
+impl std::error::Error for StripPresetError {}
+
+impl Display for StripPresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown preset '{}'. Expected one of privacy, web or minimal",
+            self.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_critical_chunks_are_never_removed() {
+        for preset in &[StripPreset::Privacy, StripPreset::Web, StripPreset::Minimal] {
+            assert!(!preset.removes(&chunk("IHDR", &[0; 13])));
+            assert!(!preset.removes(&chunk("IDAT", &[1, 2, 3])));
+        }
+    }
+
+    #[test]
+    fn test_privacy() {
+        let preset = StripPreset::Privacy;
+
+        assert!(preset.removes(&chunk("tIME", &[7, 228, 1, 1, 0, 0, 0])));
+        assert!(preset.removes(&chunk("eXIf", b"MM\0*")));
+        assert!(preset.removes(&chunk("tEXt", b"Software\0GIMP")));
+        assert!(preset.removes(&chunk("iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>")));
+        assert!(!preset.removes(&chunk("tEXt", b"Title\0Dice")));
+        assert!(!preset.removes(&chunk("gAMA", &[0, 0, 177, 143])));
+        assert!(!preset.removes(&chunk("ruSt", b"secret")));
+    }
+
+    #[test]
+    fn test_web() {
+        let preset = StripPreset::Web;
+
+        assert!(!preset.removes(&chunk("sRGB", &[0])));
+        assert!(!preset.removes(&chunk("tRNS", &[0])));
+        assert!(preset.removes(&chunk("tEXt", b"Title\0Dice")));
+        assert!(preset.removes(&chunk("ruSt", b"secret")));
+    }
+
+    #[test]
+    fn test_minimal() {
+        let preset = StripPreset::Minimal;
+
+        assert!(!preset.removes(&chunk("tRNS", &[0])));
+        assert!(!preset.removes(&chunk("fdAT", &[0, 0, 0, 1])));
+        assert!(preset.removes(&chunk("sRGB", &[0])));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            StripPreset::from_str("privacy").unwrap(),
+            StripPreset::Privacy
+        );
+        assert!(StripPreset::from_str("everything").is_err());
+    }
+}